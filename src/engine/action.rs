@@ -0,0 +1,229 @@
+use std::fmt;
+
+use engine::Point;
+use engine::level::{self, Level, Step};
+use engine::program::{Ability, Program, ProgramId, Team};
+
+// Everything a side can do to the level. The player's UI and the AI both
+// go through `Action::apply`, so neither can break the rules.
+#[derive(Clone,Debug)]
+pub enum Action {
+    // Load a program from the roster onto an uplink
+    Deploy(Program, Point),
+    // Step the program onto the adjacent tile
+    Move(ProgramId, Point),
+    UseAbility(ProgramId, Ability, Point),
+    EndTurn(Team),
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum ActionError {
+    NotOnLevel,
+    // It is the other team's turn
+    NotYourTurn(Team),
+    NotAnUplink(Point),
+    NotAdjacent(Point),
+    Blocked(Point),
+    OutOfMoves,
+    Stunned,
+    AbilityUsed,
+    UnknownAbility,
+    InvalidTarget(Point),
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ActionError::NotOnLevel => write!(f, "that program is not on the level"),
+            ActionError::NotYourTurn(team) => write!(f, "it is the {:?} team's turn", team),
+            ActionError::NotAnUplink(p) => write!(f, "{},{} is not an uplink", p.x, p.y),
+            ActionError::NotAdjacent(p) => write!(f, "{},{} is not next to the program", p.x, p.y),
+            ActionError::Blocked(p) => write!(f, "{},{} is blocked", p.x, p.y),
+            ActionError::OutOfMoves => write!(f, "not enough moves left"),
+            ActionError::Stunned => write!(f, "the program is stunned"),
+            ActionError::AbilityUsed => write!(f, "an ability was already used this turn"),
+            ActionError::UnknownAbility => write!(f, "the program has no such ability"),
+            ActionError::InvalidTarget(p) => write!(f, "nothing to target at {},{}", p.x, p.y),
+        }
+    }
+}
+
+fn check_turn(level: &Level, team: Team) -> Result<(), ActionError> {
+    if team == level.turn {
+        Ok(())
+    }
+    else {
+        Err(ActionError::NotYourTurn(level.turn))
+    }
+}
+
+fn on_level(level: &Level, id: ProgramId) -> Result<&Program, ActionError> {
+    level.programs.get(&id).ok_or(ActionError::NotOnLevel)
+}
+
+// The step the program would take onto `to`
+fn move_step(level: &Level, program: &Program, to: Point) -> Result<Step, ActionError> {
+    if level::direction_between(program.position, to).is_none() {
+        return Err(ActionError::NotAdjacent(to));
+    }
+    if program.stunned() {
+        return Err(ActionError::Stunned);
+    }
+    let step = match level.step(program.position, to) {
+        Some(step) => step,
+        None => return Err(ActionError::Blocked(to)),
+    };
+    if step.cost > program.moves_left() {
+        return Err(ActionError::OutOfMoves);
+    }
+    Ok(step)
+}
+
+impl Action {
+    pub fn validate(&self, level: &Level) -> Result<(), ActionError> {
+        match *self {
+            Action::Deploy(ref program, uplink) => {
+                try!(check_turn(level, program.team));
+                if !level.tile(uplink).is_uplink() {
+                    return Err(ActionError::NotAnUplink(uplink));
+                }
+                Ok(())
+            }
+            Action::Move(id, to) => {
+                let program = try!(on_level(level, id));
+                try!(check_turn(level, program.team));
+                move_step(level, program, to).map(|_| ())
+            }
+            Action::UseAbility(id, ability, target) => {
+                let caster = try!(on_level(level, id));
+                try!(check_turn(level, caster.team));
+                if caster.stunned() {
+                    return Err(ActionError::Stunned);
+                }
+                if caster.turn_state.ability_used {
+                    return Err(ActionError::AbilityUsed);
+                }
+                if !caster.abilities.iter().any(|&(_, a)| a == ability) {
+                    return Err(ActionError::UnknownAbility);
+                }
+                if !ability.valid_target(level, caster, target) {
+                    return Err(ActionError::InvalidTarget(target));
+                }
+                Ok(())
+            }
+            Action::EndTurn(team) => check_turn(level, team),
+        }
+    }
+
+    // Carries out the action if it is legal. True if it left damage or
+    // repairs for `engine::resolve` to play out.
+    pub fn apply(self, level: &mut Level) -> Result<bool, ActionError> {
+        try!(self.validate(level));
+        match self {
+            Action::Deploy(mut program, uplink) => {
+                program.position = uplink;
+                level.remove_uplink_at(uplink);
+                level.add_program(program);
+                Ok(false)
+            }
+            Action::Move(id, to) => {
+                let step = try!(move_step(level, &level.programs[&id], to));
+                level.programs.get_mut(&id).unwrap().move_to(step);
+                Ok(false)
+            }
+            Action::UseAbility(id, ability, target) => {
+                level.programs.get_mut(&id).unwrap().turn_state.ability_used = true;
+                ability.apply(level, id, target);
+                Ok(true)
+            }
+            Action::EndTurn(team) => Ok(level.end_turn(team)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Point;
+    use engine::level::Level;
+    use engine::program::{Ability, Program, ProgramId, StatusEffect, Team};
+    use super::{Action, ActionError};
+
+    // A player program at 2,1 next to an enemy at 3,1, with an uplink
+    // in the corner and a slow tile below the player
+    fn setup() -> (Level, ProgramId, ProgramId) {
+        let rows = vec!["o....".to_owned(), ".~...".to_owned()];
+        let mut level = Level::new(&rows);
+        let mut hack = Program::new(Team::Player, Point::new(2, 1), "Hack");
+        hack.abilities.push(("Slice".to_owned(), Ability::destroy(2, 1)));
+        let hack = level.add_program(hack);
+        let patrol = level.add_program(Program::new(Team::Enemy, Point::new(3, 1), "Patrol"));
+        (level, hack, patrol)
+    }
+
+    fn check(level: &Level, action: Action) -> Result<(), ActionError> {
+        action.validate(level)
+    }
+
+    #[test]
+    fn allows_legal_actions() {
+        let (level, hack, _) = setup();
+        let deploy = Program::new(Team::Player, Point::new(0, 0), "Bug");
+        assert_eq!(check(&level, Action::Deploy(deploy, Point::new(1, 1))), Ok(()));
+        assert_eq!(check(&level, Action::Move(hack, Point::new(2, 2))), Ok(()));
+        assert_eq!(check(&level, Action::UseAbility(hack, Ability::destroy(2, 1), Point::new(3, 1))), Ok(()));
+        assert_eq!(check(&level, Action::EndTurn(Team::Player)), Ok(()));
+    }
+
+    #[test]
+    fn refuses_programs_off_the_level() {
+        let (level, _, _) = setup();
+        assert_eq!(check(&level, Action::Move(ProgramId(9), Point::new(2, 2))), Err(ActionError::NotOnLevel));
+        assert_eq!(check(&level, Action::UseAbility(ProgramId(9), Ability::destroy(2, 1), Point::new(3, 1))),
+                   Err(ActionError::NotOnLevel));
+    }
+
+    #[test]
+    fn refuses_the_other_team() {
+        let (level, _, patrol) = setup();
+        let deploy = Program::new(Team::Enemy, Point::new(0, 0), "Bug");
+        assert_eq!(check(&level, Action::Deploy(deploy, Point::new(1, 1))), Err(ActionError::NotYourTurn(Team::Player)));
+        assert_eq!(check(&level, Action::Move(patrol, Point::new(4, 1))), Err(ActionError::NotYourTurn(Team::Player)));
+        assert_eq!(check(&level, Action::EndTurn(Team::Enemy)), Err(ActionError::NotYourTurn(Team::Player)));
+    }
+
+    #[test]
+    fn refuses_bad_moves() {
+        let (mut level, hack, _) = setup();
+        let deploy = Program::new(Team::Player, Point::new(0, 0), "Bug");
+        assert_eq!(check(&level, Action::Deploy(deploy, Point::new(4, 2))), Err(ActionError::NotAnUplink(Point::new(4, 2))));
+        assert_eq!(check(&level, Action::Move(hack, Point::new(3, 2))), Err(ActionError::NotAdjacent(Point::new(3, 2))));
+        assert_eq!(check(&level, Action::Move(hack, Point::new(3, 1))), Err(ActionError::Blocked(Point::new(3, 1))));
+        assert_eq!(check(&level, Action::Move(hack, Point::new(1, 1))), Err(ActionError::Blocked(Point::new(1, 1))));
+
+        level.programs.get_mut(&hack).unwrap().turn_state.moves_made = 2;
+        assert_eq!(check(&level, Action::Move(hack, Point::new(2, 2))), Err(ActionError::OutOfMoves));
+    }
+
+    #[test]
+    fn refuses_stunned_programs() {
+        let (mut level, hack, _) = setup();
+        level.programs.get_mut(&hack).unwrap().status_effects.push(StatusEffect::Stun { turns: 1 });
+        assert_eq!(check(&level, Action::Move(hack, Point::new(2, 2))), Err(ActionError::Stunned));
+        assert_eq!(check(&level, Action::UseAbility(hack, Ability::destroy(2, 1), Point::new(3, 1))), Err(ActionError::Stunned));
+    }
+
+    #[test]
+    fn refuses_bad_abilities() {
+        let (mut level, hack, _) = setup();
+        assert_eq!(check(&level, Action::UseAbility(hack, Ability::destroy(5, 1), Point::new(3, 1))),
+                   Err(ActionError::UnknownAbility));
+        assert_eq!(check(&level, Action::UseAbility(hack, Ability::destroy(2, 1), Point::new(2, 2))),
+                   Err(ActionError::InvalidTarget(Point::new(2, 2))));
+        assert_eq!(check(&level, Action::UseAbility(hack, Ability::destroy(2, 1), Point::new(5, 1))),
+                   Err(ActionError::InvalidTarget(Point::new(5, 1))));
+
+        level.programs.get_mut(&hack).unwrap().turn_state.ability_used = true;
+        assert_eq!(check(&level, Action::UseAbility(hack, Ability::destroy(2, 1), Point::new(3, 1))),
+                   Err(ActionError::AbilityUsed));
+    }
+}
@@ -0,0 +1,775 @@
+use std::fmt;
+
+use engine::Point;
+use engine::action::Action;
+use engine::level::{self, CellContents, Level};
+use engine::pathfinding;
+use engine::program::{Ability, AbilityKind, Program, ProgramId, StatusEffect, Team};
+use engine::search::{self, PlanStep};
+use engine::tile::{self, Tile};
+
+const DEAD_END_PENALTY: i32 = 15;
+// For ending a turn on a hazard that would delete the program
+const HAZARD_PENALTY: i32 = 40;
+const KILL_BONUS: i32 = 50;
+// How many chosen steps the debug log keeps
+const LOG_LENGTH: usize = 40;
+// Ticks to wait after a program finishes acting, so the player can follow
+const PAUSE_TICKS: usize = 4;
+
+#[derive(Clone,Copy,Debug)]
+pub enum AIChoice {
+    Ability {
+        ability: Ability,
+        target: Point,
+    },
+    // Step onto the given tile
+    Move(Point),
+}
+
+#[derive(Clone,Copy,Debug)]
+pub enum AIState {
+    WaitingAnimation,
+    Plotting,
+    Done,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+// How much the lookahead AI cares about each outcome of a plan
+#[derive(Clone,Copy,Debug)]
+pub struct Weights {
+    // Per point of damage dealt
+    pub damage: i32,
+    pub kill: i32,
+    // Per point of threat (see `threat`) of each program killed
+    pub threat: i32,
+    // Per harmful timed effect on an opposing program
+    pub status: i32,
+    // Per point of repair or beneficial effect on an ally
+    pub support: i32,
+    // Per move still needed to get in range of an opposing program
+    pub distance: i32,
+    pub dead_end: i32,
+    // Per tile the other team's reply costs, when the search plays it out
+    pub reply: i32,
+}
+
+// How the lookahead AI plans. Every program's turn is planned when the
+// enemy turn starts, in the order they act, each against the level as
+// the plans before it would leave it.
+#[derive(Clone,Copy,Debug)]
+pub struct Settings {
+    // 1 plans each program's whole turn; 2 also plays out the other
+    // team's reply to the most promising plans, which is itself searched
+    // a ply shallower
+    pub depth: usize,
+    pub weights: Weights,
+}
+
+// What the AI weighed up during its last tick, for tuning
+#[derive(Clone,Debug,Default)]
+pub struct DebugInfo {
+    // Every tile and target considered by the program that last planned,
+    // with the best score found for it
+    pub considered: Vec<(Point, i32)>,
+    // The program `considered` belongs to; kept until the next one plans
+    pub scored: Option<ProgramId>,
+    // The step each program took, oldest first
+    pub log: Vec<String>,
+}
+
+pub struct AI {
+    pub difficulty: Difficulty,
+    pub debug: bool,
+    pub debug_info: DebugInfo,
+    // What each program still means to do this turn, along with what it
+    // considered while planning
+    plans: Vec<(ProgramId, Vec<PlanStep>, Vec<(Point, i32)>)>,
+    // The enemy programs in the order they act this turn, and which one
+    // is acting
+    queue: Option<Vec<ProgramId>>,
+    current: usize,
+    // Whether the acting program has done anything yet
+    acted: bool,
+    // Ticks left to pause before the next program acts
+    wait: usize,
+    skip: bool,
+    pub focus: Option<ProgramId>,
+    pub moved_from: Option<Point>,
+}
+
+impl AIChoice {
+    // Where the choice takes effect
+    pub fn tile(&self) -> Point {
+        match *self {
+            AIChoice::Ability { target, .. } => target,
+            AIChoice::Move(to) => to,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            AIChoice::Ability { target, .. } => format!("hit {},{}", target.x, target.y),
+            AIChoice::Move(to) => format!("mv {},{}", to.x, to.y),
+        }
+    }
+}
+
+impl DebugInfo {
+    // Remembers the score of a tile, keeping only the best one
+    pub fn consider(&mut self, tile: Point, score: i32) {
+        match self.considered.iter().position(|&(p, _)| p == tile) {
+            Some(index) => {
+                if score > self.considered[index].1 {
+                    self.considered[index].1 = score;
+                }
+            }
+            None => self.considered.push((tile, score)),
+        }
+    }
+
+    pub fn record(&mut self, program: &Program, what: String) {
+        self.log.push(format!("{:.8} {}", program.name, what));
+        if self.log.len() > LOG_LENGTH {
+            self.log.remove(0);
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+        }
+    }
+
+    pub fn easier(&self) -> Difficulty {
+        match *self {
+            Difficulty::Easy | Difficulty::Normal => Difficulty::Easy,
+            Difficulty::Hard => Difficulty::Normal,
+        }
+    }
+
+    pub fn harder(&self) -> Difficulty {
+        match *self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal | Difficulty::Hard => Difficulty::Hard,
+        }
+    }
+
+    // None if programs just take the best single step each tick
+    pub fn settings(&self) -> Option<Settings> {
+        match *self {
+            Difficulty::Easy => None,
+            Difficulty::Normal => Some(Settings {
+                depth: 1,
+                weights: Weights {
+                    damage: 10, kill: 40, threat: 4, status: 8, support: 6,
+                    distance: 5, dead_end: 5, reply: 0,
+                },
+            }),
+            Difficulty::Hard => Some(Settings {
+                depth: 2,
+                weights: Weights {
+                    damage: 10, kill: 60, threat: 8, status: 10, support: 8,
+                    distance: 5, dead_end: 10, reply: 10,
+                },
+            }),
+        }
+    }
+}
+
+// How an enemy program moves around. Every program picks from the same
+// ability choices; a behavior adds the moves it is willing to make.
+pub trait Behavior: fmt::Debug + Send + Sync {
+    fn plan_moves(&self, program: &Program, level: &Level, choices: &mut Vec<(i32, AIChoice)>);
+
+    // Whether the lookahead AI may plan a turn ending on `point`
+    fn allows(&self, _program: &Program, _point: Point) -> bool {
+        true
+    }
+
+    // Whether the lookahead AI should plan for the program at all, rather
+    // than leaving it to `plan_moves`
+    fn searchable(&self, _program: &Program) -> bool {
+        true
+    }
+}
+
+// Walks up and down, drifting west when blocked
+#[derive(Debug)]
+pub struct Patrol;
+
+// Chases players, but never strays more than `radius` tiles from where it
+// was loaded
+#[derive(Debug)]
+pub struct Guard {
+    pub radius: usize,
+}
+
+// Chases the nearest player it can get in range of
+#[derive(Debug)]
+pub struct Hunter;
+
+// Hunts until it is down to `min_size` tiles, then runs away
+#[derive(Debug)]
+pub struct Coward {
+    pub min_size: usize,
+}
+
+// Never moves; only uses abilities
+#[derive(Debug)]
+pub struct Turret;
+
+impl Behavior for Patrol {
+    fn plan_moves(&self, program: &Program, level: &Level, choices: &mut Vec<(i32, AIChoice)>) {
+        let Point { x, y } = program.position;
+
+        push_move(program, level, Point::new(x, y - 1), 70, choices);
+        push_move(program, level, Point::new(x, y + 1), 70, choices);
+        push_move(program, level, Point::new(x - 1, y), 50, choices);
+    }
+
+    // Patrols follow their route whatever the difficulty
+    fn searchable(&self, _program: &Program) -> bool {
+        false
+    }
+}
+
+impl Behavior for Guard {
+    fn plan_moves(&self, program: &Program, level: &Level, choices: &mut Vec<(i32, AIChoice)>) {
+        let home = program.home;
+        let radius = self.radius;
+        if pursue(program, level, choices, &|p| manhattan(p, home) <= radius) {
+            return;
+        }
+
+        // Nothing to chase nearby, so head back to the post
+        if program.position != home {
+            if let Some(path) = level.paths(program).path_to(home) {
+                push_move(program, level, path[0], 50, choices);
+            }
+        }
+    }
+
+    fn allows(&self, program: &Program, point: Point) -> bool {
+        manhattan(point, program.home) <= self.radius
+    }
+}
+
+impl Behavior for Hunter {
+    fn plan_moves(&self, program: &Program, level: &Level, choices: &mut Vec<(i32, AIChoice)>) {
+        pursue(program, level, choices, &|_| true);
+    }
+}
+
+impl Behavior for Coward {
+    fn plan_moves(&self, program: &Program, level: &Level, choices: &mut Vec<(i32, AIChoice)>) {
+        if program.size() > self.min_size {
+            pursue(program, level, choices, &|_| true);
+        }
+        else {
+            retreat(program, level, choices);
+        }
+    }
+
+    fn searchable(&self, program: &Program) -> bool {
+        program.size() > self.min_size
+    }
+}
+
+impl Behavior for Turret {
+    fn plan_moves(&self, _program: &Program, _level: &Level, _choices: &mut Vec<(i32, AIChoice)>) {
+    }
+
+    fn allows(&self, program: &Program, point: Point) -> bool {
+        point == program.position
+    }
+}
+
+// Damage already on its way to the program but not yet taken
+pub fn pending_damage(program: &Program) -> usize {
+    program.status_effects.iter().map(|effect| {
+        match *effect {
+            StatusEffect::Damage(damage) => damage,
+            _ => 0,
+        }
+    }).sum()
+}
+
+// Damage an ability does to its target
+pub fn ability_damage(ability: &Ability) -> usize {
+    match ability.kind {
+        AbilityKind::Destroy { damage } => damage,
+        AbilityKind::Status(_) | AbilityKind::DeleteSector | AbilityKind::CreateSector | AbilityKind::Repair { .. } => 0,
+    }
+}
+
+// How much damage the program can do in one go
+pub fn threat(program: &Program) -> usize {
+    program.abilities.iter()
+        .filter(|&&(_, ref ability)| ability.offensive())
+        .map(|&(_, ref ability)| ability_damage(ability))
+        .max()
+        .unwrap_or(0)
+}
+
+// How worthwhile it is to use an offensive ability on `target`: programs
+// it would finish off come first, then the most dangerous and the most
+// worn down. Only used for single steps, which always see the level with
+// earlier damage resolved; None if it hits no opposing program.
+pub fn target_value(level: &Level, caster: &Program, ability: &Ability, target: Point) -> Option<i32> {
+    let damage = ability_damage(ability);
+    let mut hit: Vec<ProgramId> = vec![];
+    let mut value = None;
+    for tile in ability.affected_tiles(level, caster, target) {
+        let id = match level.contents_of(tile) {
+            CellContents::Program(id) => id,
+            _ => continue,
+        };
+        let program = &level.programs[&id];
+        if program.team == caster.team || hit.contains(&id) {
+            continue;
+        }
+        hit.push(id);
+
+        let size = program.size();
+        let mut score = 5 * threat(program) as i32 + 2 * program.max_tail.saturating_sub(size) as i32;
+        // Only damage can finish a program off; a program on the level
+        // always has a tile left, so status effects never count as a kill
+        if damage >= size {
+            score += KILL_BONUS;
+        }
+        value = Some(value.unwrap_or(0) + score);
+    }
+    value
+}
+
+pub fn manhattan(a: Point, b: Point) -> usize {
+    let dx = (a.x as isize - b.x as isize).abs();
+    let dy = (a.y as isize - b.y as isize).abs();
+    (dx + dy) as usize
+}
+
+fn nearest_opponent(level: &Level, team: Team, point: Point) -> Option<usize> {
+    level.programs.values()
+        .filter(|program| program.team != team)
+        .flat_map(|program| program.tiles())
+        .map(|tile| manhattan(tile, point))
+        .min()
+}
+
+fn push_move(program: &Program, level: &Level, to: Point, score: i32, choices: &mut Vec<(i32, AIChoice)>) {
+    if let Some(step) = level.step(program.position, to) {
+        if step.cost <= program.moves_left() {
+            choices.push((score, AIChoice::Move(to)));
+        }
+    }
+}
+
+// Tiles from which one of the program's offensive abilities can hit an
+// opposing program
+pub fn attack_tiles(level: &Level, program: &Program) -> Vec<Point> {
+    let mut result = vec![];
+    for &(_, ability) in program.abilities.iter() {
+        if !ability.offensive() {
+            continue;
+        }
+        for other in level.programs.values() {
+            if other.team == program.team {
+                continue;
+            }
+            for tile in other.tiles() {
+                for p in ability.reachable_tiles(tile) {
+                    let free = p == program.position || level.passable(p);
+                    if !free || result.contains(&p) {
+                        continue;
+                    }
+                    if !ability.line_of_sight || level.line_of_sight(p, tile) {
+                        result.push(p);
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+// A tile with at most one way out is easy to get boxed into
+pub fn dead_end(level: &Level, point: Point) -> bool {
+    level::neighbors(point).into_iter().filter(|&n| level.tile(n).passable()).count() <= 1
+}
+
+// What it costs the program to end its turn on `point`: the hazard
+// damage as a share of what is left of it, so a hazard that would delete
+// it costs the full penalty
+pub fn hazard_penalty(level: &Level, program: &Program, point: Point) -> i32 {
+    if level.tile(point) != Tile::Hazard {
+        return 0;
+    }
+    let remaining = program.size().saturating_sub(pending_damage(program));
+    if remaining == 0 {
+        return 0;
+    }
+    let damage = ::std::cmp::min(tile::HAZARD_DAMAGE, remaining);
+    HAZARD_PENALTY * damage as i32 / remaining as i32
+}
+
+fn pursuit_score(level: &Level, program: &Program, point: Point, distance: usize) -> i32 {
+    let mut score = -(distance as i32) * 10;
+    if dead_end(level, point) {
+        score -= DEAD_END_PENALTY;
+    }
+    score - hazard_penalty(level, program, point)
+}
+
+// Head for the tile, within this turn's moves, that is the shortest path
+// away from being able to attack. Only tiles passing `allowed` are
+// considered; false if none of them lead anywhere useful.
+fn pursue(program: &Program, level: &Level, choices: &mut Vec<(i32, AIChoice)>, allowed: &Fn(Point) -> bool) -> bool {
+    let origin = program.position;
+    let targets = attack_tiles(level, program);
+    let paths = level.paths(program);
+    if targets.is_empty() {
+        return false;
+    }
+    let field = pathfinding::distance_field(level, &targets);
+
+    // The program's own head blocks the field, so staying put is
+    // measured through its neighbors
+    let stay = if !allowed(origin) {
+        None
+    }
+    else if targets.contains(&origin) {
+        Some(0)
+    }
+    else {
+        level::neighbors(origin).into_iter()
+            .filter_map(|n| level.step(origin, n))
+            .filter_map(|step| field.distance(step.destination).map(|d| d + step.cost))
+            .min()
+    };
+
+    let mut best = stay.map(|distance| (pursuit_score(level, program, origin, distance), 0, origin));
+    for destination in paths.reachable() {
+        if !allowed(destination) {
+            continue;
+        }
+        let distance = match field.distance(destination) {
+            Some(distance) => distance,
+            None => continue,
+        };
+        let cost = paths.cost_to(destination).unwrap_or(0);
+        let candidate = (pursuit_score(level, program, destination, distance), cost, destination);
+        best = match best {
+            // Prefer the cheaper route when the scores tie
+            Some(current) if current.0 > candidate.0 || (current.0 == candidate.0 && current.1 <= candidate.1) => Some(current),
+            _ => Some(candidate),
+        };
+    }
+
+    match best {
+        Some((_, _, destination)) => {
+            if destination != origin {
+                if let Some(path) = paths.path_to(destination) {
+                    push_move(program, level, path[0], 50, choices);
+                }
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+// Get as far from the opposing programs as this turn's moves allow
+fn retreat(program: &Program, level: &Level, choices: &mut Vec<(i32, AIChoice)>) {
+    let (team, origin) = (program.team, program.position);
+    let paths = level.paths(program);
+
+    let safety = |point: Point| -> i32 {
+        let mut score = nearest_opponent(level, team, point).unwrap_or(0) as i32 * 10;
+        if dead_end(level, point) {
+            score -= DEAD_END_PENALTY;
+        }
+        score - hazard_penalty(level, program, point)
+    };
+
+    let mut best = (safety(origin), origin);
+    for destination in paths.reachable() {
+        let score = safety(destination);
+        if score > best.0 {
+            best = (score, destination);
+        }
+    }
+
+    if best.1 != origin {
+        if let Some(path) = paths.path_to(best.1) {
+            push_move(program, level, path[0], 50, choices);
+        }
+    }
+}
+
+// The best single step for the program right now, ignoring the rest of
+// its turn. Everything it weighed up goes into `debug`.
+fn greedy_choice(program: &Program, level: &Level, debug: &mut DebugInfo) -> Option<AIChoice> {
+    let mut choices = vec![];
+
+    if program.can_use_ability() {
+        for &(_, ability) in program.abilities.iter() {
+            let score = match ability.kind {
+                AbilityKind::Destroy { .. } => 100,
+                AbilityKind::Repair { .. } => 80,
+                AbilityKind::Status(effect) => if effect.beneficial() { 40 } else { 60 },
+                AbilityKind::DeleteSector | AbilityKind::CreateSector => continue,
+            };
+            for target in ability.targets(level, program) {
+                let score = if ability.offensive() {
+                    match target_value(level, program, &ability, target) {
+                        Some(value) => score + value,
+                        None => continue,
+                    }
+                }
+                else {
+                    score
+                };
+                choices.push((score, AIChoice::Ability {
+                    ability: ability,
+                    target: target,
+                }));
+            }
+        }
+    }
+
+    if program.can_move() {
+        match program.behavior {
+            Some(ref behavior) => behavior.plan_moves(program, level, &mut choices),
+            None => Hunter.plan_moves(program, level, &mut choices),
+        }
+    }
+
+    for &(score, ref choice) in choices.iter() {
+        debug.consider(choice.tile(), score);
+    }
+
+    choices.sort_by(|&(s1, _), &(s2, _)| { s2.cmp(&s1) });
+    if choices.is_empty() {
+        None
+    }
+    else {
+        Some(choices.remove(0).1)
+    }
+}
+
+impl AI {
+    pub fn new(difficulty: Difficulty) -> AI {
+        AI {
+            difficulty: difficulty,
+            debug: false,
+            debug_info: DebugInfo::default(),
+            plans: vec![],
+            queue: None,
+            current: 0,
+            acted: false,
+            wait: 0,
+            skip: false,
+            focus: None,
+            moved_from: None,
+        }
+    }
+
+    // Forget last turn's plans
+    pub fn begin_turn(&mut self) {
+        self.plans.clear();
+        self.debug_info = DebugInfo::default();
+        self.queue = None;
+        self.current = 0;
+        self.acted = false;
+        self.wait = 0;
+        self.skip = false;
+        self.focus = None;
+        self.moved_from = None;
+    }
+
+    // How to plan the program's turn; None if it takes the best single
+    // step each tick instead
+    fn search_settings(&self, program: &Program) -> Option<Settings> {
+        if program.behavior.as_ref().map_or(true, |behavior| behavior.searchable(program)) {
+            self.difficulty.settings()
+        }
+        else {
+            None
+        }
+    }
+
+    // Plans the turn of every program that searches, in the order they
+    // act. Each plan is carried out on a copy of the level before the next
+    // program plans, without resolving it, so the damage it deals is
+    // still pending there and later programs don't pile onto a target
+    // that is already doomed.
+    fn plan_all(&mut self, level: &Level, queue: &[ProgramId]) {
+        let mut projected = level.clone();
+        for &id in queue {
+            let settings = match projected.programs.get(&id).and_then(|program| self.search_settings(program)) {
+                Some(settings) => settings,
+                None => continue,
+            };
+            let mut considered = vec![];
+            let plan = search::plan_turn(&projected, id, &settings, &mut considered);
+            for &step in plan.iter() {
+                if !search::execute(&mut projected, id, step) {
+                    break;
+                }
+            }
+            self.plans.push((id, plan, considered));
+        }
+    }
+
+    // The next step of the program's plan for this turn, planning it
+    // now if it wasn't when the turn started
+    fn planned_step(&mut self, level: &Level, id: ProgramId, settings: &Settings) -> Option<PlanStep> {
+        let known = self.plans.iter().position(|&(planned, _, _)| planned == id);
+        let entry = match known {
+            Some(entry) => entry,
+            None => {
+                let mut considered = vec![];
+                let plan = search::plan_turn(level, id, settings, &mut considered);
+                self.plans.push((id, plan, considered));
+                self.plans.len() - 1
+            }
+        };
+
+        let (_, ref mut plan, ref considered) = self.plans[entry];
+        if self.debug_info.scored != Some(id) {
+            self.debug_info.considered.clear();
+            self.debug_info.scored = Some(id);
+            for &(tile, score) in considered.iter() {
+                self.debug_info.consider(tile, score);
+            }
+        }
+        if plan.is_empty() {
+            None
+        }
+        else {
+            Some(plan.remove(0))
+        }
+    }
+
+    // The enemy program whose turn it is, moving on to the next one once
+    // `current` is past the end or no longer on the level
+    fn acting(&mut self, level: &Level) -> Option<ProgramId> {
+        if self.queue.is_none() {
+            let queue = level.team(Team::Enemy);
+            self.plan_all(level, &queue);
+            self.queue = Some(queue);
+        }
+
+        let queue = self.queue.as_ref().unwrap();
+        while self.current < queue.len() {
+            let id = queue[self.current];
+            if level.programs.contains_key(&id) {
+                return Some(id);
+            }
+            self.current += 1;
+        }
+        None
+    }
+
+    // Takes one step for the program; None if it has nothing left to do
+    fn act(&mut self, level: &mut Level, id: ProgramId) -> Option<AIState> {
+        if let Some(settings) = self.search_settings(&level.programs[&id]) {
+            let step = match self.planned_step(level, id, &settings) {
+                Some(step) => step,
+                None => return None,
+            };
+            if !search::execute(level, id, step) {
+                // Something got in the way; give up on the rest
+                for &mut (planned, ref mut plan, _) in self.plans.iter_mut() {
+                    if planned == id {
+                        plan.clear();
+                    }
+                }
+                return None;
+            }
+            if let Some(program) = level.programs.get(&id) {
+                self.debug_info.record(program, step.choice().describe());
+            }
+            return match step {
+                PlanStep::UseAbility(..) => Some(AIState::WaitingAnimation),
+                PlanStep::Move(_) => Some(AIState::Plotting),
+            };
+        }
+
+        // Single steps are planned afresh every time
+        self.debug_info.considered.clear();
+        self.debug_info.scored = Some(id);
+        let choice = greedy_choice(&level.programs[&id], level, &mut self.debug_info);
+        if let Some(choice) = choice {
+            self.debug_info.record(&level.programs[&id], choice.describe());
+        }
+        let (action, state) = match choice {
+            Some(AIChoice::Ability { ability, target }) => {
+                (Action::UseAbility(id, ability, target), AIState::WaitingAnimation)
+            }
+            Some(AIChoice::Move(to)) => (Action::Move(id, to), AIState::Plotting),
+            None => return None,
+        };
+        action.apply(level).ok().map(|_| state)
+    }
+
+    // Skip the pauses for the rest of this turn
+    pub fn fast_forward(&mut self) {
+        self.skip = true;
+    }
+
+    // Enemy programs act one at a time, taking one step per tick. `focus`
+    // is left on whichever one is acting, and `moved_from` on where it
+    // stepped from, if it moved.
+    pub fn tick(&mut self, level: &mut Level) -> AIState {
+        self.moved_from = None;
+        if self.wait > 0 && !self.skip {
+            self.wait -= 1;
+            return AIState::Plotting;
+        }
+        self.wait = 0;
+
+        loop {
+            let id = match self.acting(level) {
+                Some(id) => id,
+                None => {
+                    self.focus = None;
+                    return AIState::Done;
+                }
+            };
+            self.focus = Some(id);
+
+            let before = level.programs[&id].position;
+            if let Some(state) = self.act(level, id) {
+                let after = level.programs.get(&id).map(|program| program.position);
+                if after != Some(before) {
+                    self.moved_from = Some(before);
+                }
+                self.acted = true;
+                return state;
+            }
+
+            // Done with this program; let the player see where it ended
+            // up before moving on
+            self.current += 1;
+            if self.acted {
+                self.acted = false;
+                self.wait = PAUSE_TICKS;
+                if !self.skip {
+                    return AIState::Plotting;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+
+use engine::Point;
+use engine::ai::{Coward, Guard, Hunter, Patrol, Turret};
+use engine::level;
+use engine::level_file::{self, LevelFile, ParseError};
+use engine::program::{Ability, ProgramBuilder, Shape, StatusEffect, Team};
+use engine::save_file::{self, SaveFile};
+
+const LEVEL_DIR: &'static str = "levels";
+const SAVE_PATH: &'static str = "mission.save";
+
+lazy_static! {
+    static ref PROGRAMS: HashMap<String, ProgramBuilder> = {
+        let mut m = HashMap::new();
+
+        m.insert("Sprinter".to_owned(),
+                 ProgramBuilder::new("Sprinter")
+                 .ability("Overflow", Ability::destroy(1, 3).line_of_sight(true))
+                 .max_tail(2)
+                 .max_moves(10)
+                 .behavior(Hunter));
+
+        m.insert("Patrol".to_owned(),
+                 ProgramBuilder::new("Patrol")
+                 .ability("Delete", Ability::destroy(4, 1))
+                 .max_tail(6)
+                 .max_moves(2)
+                 .behavior(Patrol));
+
+        m.insert("Firewall".to_owned(),
+                 ProgramBuilder::new("Firewall")
+                 .ability("Reject", Ability::destroy(6, 2))
+                 .ability("Flare", Ability::destroy(2, 3).shape(Shape::Cross(1)).line_of_sight(true))
+                 .max_tail(1)
+                 .max_moves(0)
+                 .behavior(Turret));
+
+        m.insert("Medic".to_owned(),
+                 ProgramBuilder::new("Medic")
+                 .ability("Restore", Ability::repair(3, 2))
+                 .max_tail(3)
+                 .max_moves(2)
+                 .behavior(Guard { radius: 4 }));
+
+        m.insert("Jammer".to_owned(),
+                 ProgramBuilder::new("Jammer")
+                 .ability("Freeze", Ability::status(StatusEffect::Stun { turns: 1 }, 3).line_of_sight(true))
+                 .ability("Lag", Ability::status(StatusEffect::Slowed { amount: 2, turns: 2 }, 3).line_of_sight(true))
+                 .max_tail(3)
+                 .max_moves(3)
+                 .behavior(Coward { min_size: 2 }));
+
+        m
+    };
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    NotFound(usize),
+    // There is no saved mission to resume
+    NoSave,
+    Io(String, io::Error),
+    Parse(String, ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::NotFound(id) => write!(f, "level {} does not exist", id),
+            LoadError::NoSave => write!(f, "no saved mission"),
+            LoadError::Io(ref path, ref err) => write!(f, "{}: {}", path, err),
+            LoadError::Parse(ref path, ref err) => write!(f, "{}: {}", path, err),
+        }
+    }
+}
+
+pub fn level_path(id: usize) -> String {
+    format!("{}/{:02}.level", LEVEL_DIR, id)
+}
+
+pub fn read_source(path: &str) -> io::Result<String> {
+    let mut source = String::new();
+    let mut file = try!(File::open(path));
+    try!(file.read_to_string(&mut source));
+    Ok(source)
+}
+
+pub fn load_level_file(id: usize) -> Result<LevelFile, LoadError> {
+    let path = level_path(id);
+    let source = match read_source(&path) {
+        Ok(source) => source,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Err(LoadError::NotFound(id)),
+        Err(err) => return Err(LoadError::Io(path, err)),
+    };
+
+    let parsed = match level_file::parse(&source) {
+        Ok(parsed) => parsed,
+        Err(err) => return Err(LoadError::Parse(path, err)),
+    };
+
+    for entry in parsed.legend.iter() {
+        if !PROGRAMS.contains_key(&entry.program) {
+            let err = ParseError::new(entry.line, entry.column, format!("unknown program '{}'", entry.program));
+            return Err(LoadError::Parse(path, err));
+        }
+    }
+
+    Ok(parsed)
+}
+
+pub fn load_level(id: usize) -> Result<level::Level, LoadError> {
+    let file = try!(load_level_file(id));
+
+    let layout: Vec<String> = file.layout.iter().map(|row| {
+        row.chars().map(|c| if level_file::is_terrain(c) { c } else { '.' }).collect()
+    }).collect();
+    let mut level = level::Level::new(&layout);
+    level.title = file.title.clone();
+
+    for (row_offset, row) in file.layout.iter().enumerate() {
+        for (col_offset, c) in row.chars().enumerate() {
+            if let Some(entry) = file.legend_entry(c) {
+                let builder = &PROGRAMS[&entry.program];
+                let mut instance = builder.instance(Team::Enemy);
+                instance.position = Point::new(col_offset as u16 + 1, row_offset as u16 + 1);
+                level.add_program(instance);
+            }
+        }
+    }
+
+    Ok(level)
+}
+
+pub fn has_saved_mission() -> bool {
+    fs::metadata(SAVE_PATH).is_ok()
+}
+
+pub fn save_mission(save: &SaveFile) -> Result<(), LoadError> {
+    let result = File::create(SAVE_PATH).and_then(|mut file| file.write_all(save_file::write(save).as_bytes()));
+    result.map_err(|err| LoadError::Io(SAVE_PATH.to_owned(), err))
+}
+
+pub fn load_mission() -> Result<SaveFile, LoadError> {
+    let source = match read_source(SAVE_PATH) {
+        Ok(source) => source,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Err(LoadError::NoSave),
+        Err(err) => return Err(LoadError::Io(SAVE_PATH.to_owned(), err)),
+    };
+    let mut save = match save_file::parse(&source) {
+        Ok(save) => save,
+        Err(err) => return Err(LoadError::Parse(SAVE_PATH.to_owned(), err)),
+    };
+
+    // Behaviors aren't saved; enemies get theirs back from the program
+    // they were made from
+    for program in save.level.programs.values_mut() {
+        if program.team == Team::Enemy {
+            program.behavior = PROGRAMS.get(&program.name).and_then(|builder| builder.instance(Team::Enemy).behavior);
+        }
+    }
+    Ok(save)
+}
+
+// Forgets the saved mission once it is over
+pub fn clear_mission() {
+    let _ = fs::remove_file(SAVE_PATH);
+}
@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+
+use engine::{Direction, Point};
+use engine::action::ActionError;
+use engine::pathfinding::{self, Paths};
+use engine::program::{Program, ProgramId, Team};
+use engine::tile::{self, Tile};
+
+// Cloning gives a copy whose programs can be changed without touching
+// this level's; programs keep their IDs in the copy
+#[derive(Clone)]
+pub struct Level {
+    height: usize,
+    width: usize,
+    pub title: String,
+    pub layout: Vec<Vec<Tile>>,
+    // Ordered by ID, which is the order the programs were added in
+    pub programs: BTreeMap<ProgramId, Program>,
+    next_id: usize,
+    // Whose turn it is
+    pub turn: Team,
+}
+
+// The result of moving a program onto an adjacent tile
+#[derive(Clone,Copy,Debug)]
+pub struct Step {
+    pub destination: Point,
+    pub cost: usize,
+}
+
+// How a program stood before a move, so that the move can be taken
+// back. Moves only change the mover, so putting it back is enough.
+#[derive(Clone,Debug)]
+pub struct MoveSnapshot {
+    program: ProgramId,
+    position: Point,
+    tail: Vec<Point>,
+    moves_made: usize,
+    ability_used: bool,
+}
+
+pub enum CellContents {
+    Unpassable,
+    Empty,
+    Program(ProgramId),
+    Uplink,
+}
+
+impl MoveSnapshot {
+    pub fn program(&self) -> ProgramId {
+        self.program
+    }
+}
+
+impl Level {
+    pub fn new(description: &[String]) -> Level {
+        let mut layout: Vec<Vec<Tile>> = Vec::new();
+        for s in description.iter() {
+            layout.push(s.chars().map(|c| Tile::from_char(c).unwrap_or(Tile::Void)).collect());
+        }
+        Level {
+            height: layout.len(),
+            width: layout[0].len(),
+            title: String::new(),
+            layout: layout,
+            programs: BTreeMap::new(),
+            next_id: 0,
+            turn: Team::Player,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Levels are indexed from (1, 1); anything outside the layout is void
+    pub fn tile(&self, point: Point) -> Tile {
+        if point.x == 0 || point.y == 0 {
+            return Tile::Void;
+        }
+
+        let row = (point.y - 1) as usize;
+        let col = (point.x - 1) as usize;
+        self.layout.get(row).and_then(|line| line.get(col)).cloned().unwrap_or(Tile::Void)
+    }
+
+    pub fn in_bounds(&self, point: Point) -> bool {
+        point.x >= 1 && point.y >= 1 &&
+            (point.x as usize) <= self.width && (point.y as usize) <= self.height
+    }
+
+    pub fn set_tile(&mut self, point: Point, tile: Tile) {
+        if self.in_bounds(point) {
+            self.layout[(point.y - 1) as usize][(point.x - 1) as usize] = tile;
+        }
+    }
+
+    pub fn remove_uplink_at(&mut self, point: Point) {
+        if self.tile(point).is_uplink() {
+            self.set_tile(point, Tile::Floor);
+        }
+    }
+
+    pub fn add_program(&mut self, mut program: Program) -> ProgramId {
+        let id = ProgramId(self.next_id);
+        self.next_id += 1;
+        program.home = program.position;
+        self.programs.insert(id, program);
+        id
+    }
+
+    // Puts back a program saved from a level, keeping its ID and home
+    pub fn restore_program(&mut self, id: ProgramId, program: Program) {
+        self.next_id = ::std::cmp::max(self.next_id, id.0 + 1);
+        self.programs.insert(id, program);
+    }
+
+    pub fn remove_program(&mut self, id: ProgramId) {
+        self.programs.remove(&id);
+    }
+
+    // The programs on the given team, in the order they were added
+    pub fn team(&self, team: Team) -> Vec<ProgramId> {
+        self.programs.iter()
+            .filter(|&(_, program)| program.team == team)
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+    pub fn begin_turn(&mut self, team: Team) {
+        self.turn = team;
+        for program in self.programs.values_mut() {
+            if program.team == team {
+                program.begin_turn();
+            }
+        }
+    }
+
+    pub fn end_turn(&mut self, team: Team) -> bool {
+        let hazards: Vec<ProgramId> = self.programs.iter()
+            .filter(|&(_, program)| program.team == team && self.tile(program.position) == Tile::Hazard)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in hazards.iter() {
+            if let Some(program) = self.programs.get_mut(id) {
+                program.add_damage(tile::HAZARD_DAMAGE);
+            }
+        }
+        for program in self.programs.values_mut() {
+            if program.team == team {
+                program.end_turn();
+            }
+        }
+        !hazards.is_empty()
+    }
+
+    // Walks a Bresenham line between the two points and checks that no
+    // tile strictly between them blocks sight
+    pub fn line_of_sight(&self, from: Point, to: Point) -> bool {
+        let (mut x, mut y) = (from.x as isize, from.y as isize);
+        let (x1, y1) = (to.x as isize, to.y as isize);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = (x1 - x).signum();
+        let sy = (y1 - y).signum();
+        let mut err = dx + dy;
+
+        loop {
+            if x == x1 && y == y1 {
+                return true;
+            }
+            if (x, y) != (from.x as isize, from.y as isize) {
+                if x <= 0 || y <= 0 || self.tile(Point::new(x as u16, y as u16)).blocks_sight() {
+                    return false;
+                }
+            }
+
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    pub fn teleporter_exit(&self, point: Point) -> Option<Point> {
+        if let Tile::Teleporter(channel) = self.tile(point) {
+            for (y, row) in self.layout.iter().enumerate() {
+                for (x, tile) in row.iter().enumerate() {
+                    let other = Point::new(x as u16 + 1, y as u16 + 1);
+                    if *tile == Tile::Teleporter(channel) && other != point {
+                        return Some(other);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // What happens if a program at `from` moves onto the adjacent tile `to`
+    pub fn step(&self, from: Point, to: Point) -> Option<Step> {
+        let direction = match direction_between(from, to) {
+            Some(direction) => direction,
+            None => return None,
+        };
+        if !self.passable(to) {
+            return None;
+        }
+
+        let tile = self.tile(to);
+        if let Tile::OneWay(allowed) = tile {
+            if allowed != direction {
+                return None;
+            }
+        }
+
+        let destination = match self.teleporter_exit(to) {
+            Some(exit) if self.passable(exit) => exit,
+            _ => to,
+        };
+
+        Some(Step {
+            destination: destination,
+            cost: tile.move_cost(),
+        })
+    }
+
+    // Where the program can go with the moves it has left this turn
+    pub fn paths(&self, program: &Program) -> Paths {
+        pathfinding::find(self, program.position, program.moves_left())
+    }
+
+    pub fn passable(&self, point: Point) -> bool {
+        if !self.tile(point).passable() {
+            return false;
+        }
+
+        !self.programs.values().any(|program| program.intersects(point))
+    }
+
+    pub fn contents_of(&self, point: Point) -> CellContents {
+        for (&id, program) in self.programs.iter() {
+            if program.intersects(point) {
+                return CellContents::Program(id);
+            }
+        }
+
+        match self.tile(point) {
+            Tile::Uplink => CellContents::Uplink,
+            tile if tile.passable() => CellContents::Empty,
+            _ => CellContents::Unpassable,
+        }
+    }
+
+    pub fn snapshot_move(&self, id: ProgramId) -> Option<MoveSnapshot> {
+        self.programs.get(&id).map(|program| MoveSnapshot {
+            program: id,
+            position: program.position,
+            tail: program.tail().to_vec(),
+            moves_made: program.turn_state.moves_made,
+            ability_used: program.turn_state.ability_used,
+        })
+    }
+
+    // Whether the program has moved since the snapshot was taken
+    pub fn moved_since(&self, snapshot: &MoveSnapshot) -> bool {
+        self.programs.get(&snapshot.program).map_or(false, |program| {
+            program.turn_state.moves_made != snapshot.moves_made
+        })
+    }
+
+    // Puts the program back where the snapshot found it. Refused once
+    // its turn is over or it has used an ability, or if another program
+    // now stands where it would go back to.
+    pub fn undo_move(&mut self, snapshot: MoveSnapshot) -> Result<(), ActionError> {
+        {
+            let program = try!(self.programs.get(&snapshot.program).ok_or(ActionError::NotOnLevel));
+            if program.team != self.turn {
+                return Err(ActionError::NotYourTurn(self.turn));
+            }
+            if program.turn_state.ability_used != snapshot.ability_used {
+                return Err(ActionError::AbilityUsed);
+            }
+        }
+        let tiles = snapshot.tail.iter().chain(Some(&snapshot.position));
+        for &tile in tiles {
+            let taken = self.programs.iter().any(|(&id, program)| id != snapshot.program && program.intersects(tile));
+            if taken {
+                return Err(ActionError::Blocked(tile));
+            }
+        }
+
+        let program = self.programs.get_mut(&snapshot.program).unwrap();
+        program.position = snapshot.position;
+        program.set_tail(snapshot.tail);
+        program.turn_state.moves_made = snapshot.moves_made;
+        Ok(())
+    }
+
+    pub fn check_victory(&mut self) -> Option<Team> {
+        let mut found_player = false;
+        let mut found_enemy = false;
+        for program in self.programs.values() {
+            match program.team {
+                Team::Player => found_player = true,
+                Team::Enemy => found_enemy = true,
+            };
+            if found_player && found_enemy {
+                break;
+            }
+        }
+        if !found_player {
+            Some(Team::Enemy)
+        }
+        else if !found_enemy {
+            Some(Team::Player)
+        }
+        else {
+            None
+        }
+    }
+}
+
+pub fn neighbors(point: Point) -> Vec<Point> {
+    let Point { x, y } = point;
+    let mut result = vec![Point::new(x + 1, y), Point::new(x, y + 1)];
+    if x > 1 {
+        result.push(Point::new(x - 1, y));
+    }
+    if y > 1 {
+        result.push(Point::new(x, y - 1));
+    }
+    result
+}
+
+pub fn direction_between(from: Point, to: Point) -> Option<Direction> {
+    if to.x == from.x && to.y + 1 == from.y {
+        Some(Direction::Up)
+    }
+    else if to.x == from.x && to.y == from.y + 1 {
+        Some(Direction::Down)
+    }
+    else if to.y == from.y && to.x + 1 == from.x {
+        Some(Direction::Left)
+    }
+    else if to.y == from.y && to.x == from.x + 1 {
+        Some(Direction::Right)
+    }
+    else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Point;
+    use engine::action::{Action, ActionError};
+    use engine::program::{Program, Team};
+    use super::Level;
+
+    #[test]
+    fn undoes_moves() {
+        let mut level = Level::new(&["....".to_owned()]);
+        let hack = level.add_program(Program::new(Team::Player, Point::new(1, 1), "Hack"));
+        let before = level.snapshot_move(hack).unwrap();
+        Action::Move(hack, Point::new(2, 1)).apply(&mut level).unwrap();
+        assert!(level.moved_since(&before));
+
+        assert_eq!(level.undo_move(before), Ok(()));
+        let program = &level.programs[&hack];
+        assert_eq!(program.position, Point::new(1, 1));
+        assert!(program.tail().is_empty());
+        assert_eq!(program.turn_state.moves_made, 0);
+    }
+
+    #[test]
+    fn refuses_to_undo_onto_other_programs() {
+        let mut level = Level::new(&["....".to_owned()]);
+        let hack = level.add_program(Program::new(Team::Player, Point::new(1, 1), "Hack"));
+        let before = level.snapshot_move(hack).unwrap();
+        Action::Move(hack, Point::new(2, 1)).apply(&mut level).unwrap();
+        // Losing the tail frees the tile the move started from
+        level.programs.get_mut(&hack).unwrap().damage();
+        level.add_program(Program::new(Team::Player, Point::new(1, 1), "Bug"));
+
+        assert_eq!(level.undo_move(before.clone()), Err(ActionError::Blocked(Point::new(1, 1))));
+        level.turn = Team::Enemy;
+        assert_eq!(level.undo_move(before), Err(ActionError::NotYourTurn(Team::Enemy)));
+        assert_eq!(level.programs[&hack].position, Point::new(2, 1));
+    }
+
+    #[test]
+    fn refuses_to_undo_after_an_ability() {
+        let mut level = Level::new(&["....".to_owned()]);
+        let hack = level.add_program(Program::new(Team::Player, Point::new(1, 1), "Hack"));
+        let before = level.snapshot_move(hack).unwrap();
+        Action::Move(hack, Point::new(2, 1)).apply(&mut level).unwrap();
+        level.programs.get_mut(&hack).unwrap().turn_state.ability_used = true;
+
+        assert_eq!(level.undo_move(before), Err(ActionError::AbilityUsed));
+    }
+}
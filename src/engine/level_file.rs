@@ -0,0 +1,251 @@
+use std::fmt;
+
+use engine::tile::Tile;
+
+// Level files are plain text:
+//
+//     # comment
+//     title: Perimeter
+//     briefing: First line of the briefing
+//     briefing: Second line of the briefing
+//
+//     legend:
+//     p = Patrol
+//
+//     layout:
+//     ....p....o
+//
+// Everything after `layout:` is the grid, read verbatim up to the end
+// of the file. Terrain is ' ' (void), '.' (floor), 'o' (uplink), '!'
+// (hazard), '~' (slow), '^v<>' (one-way) and '0'-'9' (teleporter
+// pairs). Glyphs in the legend are replaced by empty floor with an
+// enemy program on top.
+
+pub struct LegendEntry {
+    pub glyph: char,
+    pub program: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub struct LevelFile {
+    pub title: String,
+    pub briefing: Vec<String>,
+    pub legend: Vec<LegendEntry>,
+    pub layout: Vec<String>,
+    pub layout_line: usize,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+enum Section {
+    Header,
+    Legend,
+    Layout,
+}
+
+impl ParseError {
+    pub fn new<S: Into<String>>(line: usize, column: usize, message: S) -> ParseError {
+        ParseError {
+            line: line,
+            column: column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl LevelFile {
+    pub fn legend_entry(&self, glyph: char) -> Option<&LegendEntry> {
+        self.legend.iter().find(|entry| entry.glyph == glyph)
+    }
+}
+
+pub fn is_terrain(c: char) -> bool {
+    Tile::from_char(c).is_some()
+}
+
+// 1-based column of `part`, which must be a slice of `line`
+pub fn column_of(line: &str, part: &str) -> usize {
+    let offset = part.as_ptr() as usize - line.as_ptr() as usize;
+    line[..offset].chars().count() + 1
+}
+
+pub fn parse(source: &str) -> Result<LevelFile, ParseError> {
+    let mut file = LevelFile {
+        title: String::new(),
+        briefing: Vec::new(),
+        legend: Vec::new(),
+        layout: Vec::new(),
+        layout_line: 0,
+    };
+    let mut section = Section::Header;
+    let mut line_count = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        line_count = line_number;
+
+        if let Section::Layout = section {
+            file.layout.push(line.trim_right().to_owned());
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let column = column_of(line, trimmed);
+
+        match trimmed {
+            "legend:" => {
+                section = Section::Legend;
+                continue;
+            }
+            "layout:" => {
+                section = Section::Layout;
+                file.layout_line = line_number + 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match section {
+            Section::Header => {
+                let split = match trimmed.find(':') {
+                    Some(split) => split,
+                    None => return Err(ParseError::new(line_number, column, "expected 'key: value'")),
+                };
+                let key = trimmed[..split].trim();
+                let value = trimmed[split + 1..].trim();
+                match key {
+                    "title" => file.title = value.to_owned(),
+                    "briefing" => file.briefing.push(value.to_owned()),
+                    _ => return Err(ParseError::new(line_number, column, format!("unknown key '{}'", key))),
+                }
+            }
+            Section::Legend => {
+                let mut chars = trimmed.chars();
+                let glyph = chars.next().unwrap();
+                let rest = chars.as_str().trim_left();
+                if !rest.starts_with('=') {
+                    return Err(ParseError::new(line_number, column_of(line, rest), "expected '=' after glyph"));
+                }
+                let name = rest[1..].trim();
+                if name.is_empty() {
+                    return Err(ParseError::new(line_number, column_of(line, rest) + 1, "expected program name"));
+                }
+                if is_terrain(glyph) {
+                    return Err(ParseError::new(line_number, column, format!("glyph '{}' is reserved for terrain", glyph)));
+                }
+                if file.legend_entry(glyph).is_some() {
+                    return Err(ParseError::new(line_number, column, format!("glyph '{}' is defined twice", glyph)));
+                }
+                file.legend.push(LegendEntry {
+                    glyph: glyph,
+                    program: name.to_owned(),
+                    line: line_number,
+                    column: column_of(line, name),
+                });
+            }
+            Section::Layout => unreachable!(),
+        }
+    }
+
+    match section {
+        Section::Layout => {},
+        _ => return Err(ParseError::new(line_count + 1, 1, "missing 'layout:' section")),
+    }
+    if file.layout.iter().all(|row| row.is_empty()) {
+        return Err(ParseError::new(file.layout_line, 1, "layout is empty"));
+    }
+
+    for (row, line) in file.layout.iter().enumerate() {
+        for (col, c) in line.chars().enumerate() {
+            if !is_terrain(c) && file.legend_entry(c).is_none() {
+                return Err(ParseError::new(file.layout_line + row, col + 1, format!("unknown glyph '{}'", c)));
+            }
+        }
+    }
+
+    // Teleporters have to come in pairs
+    let mut teleporters: Vec<(usize, usize, usize)> = vec![(0, 0, 0); 10];
+    for (row, line) in file.layout.iter().enumerate() {
+        for (col, c) in line.chars().enumerate() {
+            if let Some(Tile::Teleporter(channel)) = Tile::from_char(c) {
+                let entry = &mut teleporters[channel as usize];
+                if entry.0 == 0 {
+                    entry.1 = file.layout_line + row;
+                    entry.2 = col + 1;
+                }
+                entry.0 += 1;
+            }
+        }
+    }
+    for (channel, &(count, line, column)) in teleporters.iter().enumerate() {
+        if count != 0 && count != 2 {
+            return Err(ParseError::new(line, column, format!("teleporter '{}' appears {} times, expected 2", channel, count)));
+        }
+    }
+
+    let width = file.layout.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+    for row in file.layout.iter_mut() {
+        let padding = width - row.chars().count();
+        row.extend(::std::iter::repeat(' ').take(padding));
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    fn error_at(source: &str) -> (usize, usize) {
+        match parse(source) {
+            Ok(_) => panic!("parsed a broken level"),
+            Err(err) => (err.line, err.column),
+        }
+    }
+
+    #[test]
+    fn parses_a_level() {
+        let file = parse("title: Test\nlegend:\np = Patrol\nlayout:\no..p\n.").unwrap();
+        assert_eq!(file.title, "Test");
+        assert_eq!(file.legend_entry('p').unwrap().column, 5);
+        assert_eq!(file.layout_line, 5);
+        assert_eq!(file.layout, vec!["o..p".to_owned(), ".   ".to_owned()]);
+    }
+
+    #[test]
+    fn reports_header_errors() {
+        assert_eq!(error_at("# comment\n  title Test\nlayout:\n."), (2, 3));
+        assert_eq!(error_at("title: Test\n  size: 3\nlayout:\n."), (2, 3));
+    }
+
+    #[test]
+    fn reports_legend_errors() {
+        assert_eq!(error_at("legend:\n  p Patrol\nlayout:\n."), (2, 5));
+        assert_eq!(error_at("legend:\np =\nlayout:\n."), (2, 4));
+        assert_eq!(error_at("legend:\n~ = Patrol\nlayout:\n."), (2, 1));
+        assert_eq!(error_at("legend:\np = Patrol\n p = Sprinter\nlayout:\n."), (3, 2));
+    }
+
+    #[test]
+    fn reports_layout_errors() {
+        assert_eq!(error_at("title: Test\n"), (2, 1));
+        assert_eq!(error_at("title: Test\nlayout:\n\n"), (3, 1));
+        assert_eq!(error_at("layout:\n....\n..x."), (3, 3));
+        assert_eq!(error_at("layout:\n.1..\n..1.\n...1"), (2, 2));
+    }
+}
@@ -0,0 +1,114 @@
+// The rules of the game, kept apart from the terminal so that games can
+// be simulated without one. The TUI modules are clients of this one;
+// nothing in here may depend on them or on voodoo.
+
+pub mod action;
+pub mod ai;
+pub mod data;
+pub mod level;
+pub mod level_file;
+pub mod pathfinding;
+pub mod program;
+pub mod save_file;
+pub mod search;
+pub mod tile;
+
+use self::level::Level;
+use self::program::{ProgramId, StatusEffect, Team};
+
+// A tile of a level. Levels are indexed from (1, 1).
+#[derive(Clone,Copy,Debug,Eq,PartialEq,Hash)]
+pub struct Point {
+    pub x: u16,
+    pub y: u16,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// What happened when pending damage and repairs were carried out
+#[derive(Clone,Debug,Default)]
+pub struct Resolution {
+    // Whether anything is still left to carry out
+    pub modified: bool,
+    // The programs that died and their teams; they are no longer on the
+    // level
+    pub killed: Vec<(ProgramId, Team)>,
+}
+
+impl Point {
+    pub fn new(x: u16, y: u16) -> Point {
+        Point {
+            x: x,
+            y: y,
+        }
+    }
+}
+
+// Carries out one point of each program's pending damage and repair.
+// Called once per tick until nothing is modified.
+pub fn resolve(level: &mut Level) -> Resolution {
+    let mut resolution = Resolution::default();
+    let mut repaired = vec![];
+    for (&id, p) in level.programs.iter_mut() {
+        let mut damaged = false;
+        for effect in p.status_effects.iter_mut() {
+            if let StatusEffect::Damage(damage) = *effect {
+                resolution.modified = true;
+                damaged = true;
+                *effect = StatusEffect::Damage(damage - 1);
+            }
+        }
+        p.status_effects.retain(|effect| *effect != StatusEffect::Damage(0));
+
+        if damaged {
+            let lived = p.damage();
+            if !lived {
+                resolution.killed.push((id, p.team));
+            }
+        }
+        else if p.status_effects.iter().any(|effect| match *effect { StatusEffect::Repair(_) => true, _ => false }) {
+            repaired.push(id);
+        }
+    }
+
+    // Grow tails once every program has been updated, since finding
+    // room for the new segment has to look at all of them. A repair is
+    // only used up by growing a segment; one with no room to grow into
+    // waits until there is, and a program at full size has nothing left
+    // to repair.
+    for id in repaired {
+        let end = level.programs[&id].tail_end();
+        let room = level::neighbors(end).into_iter().find(|&p| level.passable(p));
+        let program = level.programs.get_mut(&id).unwrap();
+        if !program.can_grow() {
+            program.status_effects.retain(|effect| match *effect { StatusEffect::Repair(_) => false, _ => true });
+            continue;
+        }
+        let room = match room {
+            Some(room) => room,
+            None => continue,
+        };
+
+        program.grow(room);
+        resolution.modified = true;
+        for effect in program.status_effects.iter_mut() {
+            if let StatusEffect::Repair(amount) = *effect {
+                *effect = StatusEffect::Repair(amount - 1);
+                break;
+            }
+        }
+        program.status_effects.retain(|effect| *effect != StatusEffect::Repair(0));
+    }
+
+    for &(id, _) in resolution.killed.iter() {
+        level.remove_program(id);
+    }
+
+    resolution
+}
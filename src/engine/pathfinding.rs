@@ -0,0 +1,296 @@
+use std::mem;
+
+use engine::Point;
+use engine::level::{self, Level};
+
+struct Node {
+    point: Point,
+    // The tile stepped onto to get here; differs from `point` after a
+    // teleporter
+    entered: Point,
+    cost: usize,
+    previous: Option<usize>,
+}
+
+// Shortest paths from one tile to everything reachable within a move
+// budget. Tiles occupied by programs (including the mover's own tail)
+// are treated as walls.
+pub struct Paths {
+    width: usize,
+    nodes: Vec<Node>,
+    index: Vec<Option<usize>>,
+}
+
+impl Paths {
+    fn slot(&self, point: Point) -> Option<usize> {
+        slot(self.width, self.index.len(), point)
+    }
+
+    fn node(&self, point: Point) -> Option<&Node> {
+        self.slot(point).and_then(|slot| self.index[slot]).map(|i| &self.nodes[i])
+    }
+
+    // Every tile the program can end up on, not counting where it starts
+    pub fn reachable(&self) -> Vec<Point> {
+        self.nodes.iter().skip(1).map(|node| node.point).collect()
+    }
+
+    pub fn cost_to(&self, point: Point) -> Option<usize> {
+        self.node(point).map(|node| node.cost)
+    }
+
+    // The tiles to step onto, in order, to get to `point`
+    pub fn path_to(&self, point: Point) -> Option<Vec<Point>> {
+        let mut current = match self.slot(point).and_then(|slot| self.index[slot]) {
+            Some(index) => index,
+            None => return None,
+        };
+
+        let mut path = vec![];
+        while let Some(previous) = self.nodes[current].previous {
+            path.push(self.nodes[current].entered);
+            current = previous;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+fn slot(width: usize, size: usize, point: Point) -> Option<usize> {
+    if point.x == 0 || point.y == 0 || width == 0 {
+        return None;
+    }
+
+    let slot = (point.y as usize - 1) * width + (point.x as usize - 1);
+    if (point.x as usize) <= width && slot < size {
+        Some(slot)
+    }
+    else {
+        None
+    }
+}
+
+// Dijkstra over the level; moves cost one or two, so a bucket per cost
+// is enough for a priority queue.
+pub fn find(level: &Level, origin: Point, budget: usize) -> Paths {
+    let width = level.width();
+    let size = width * level.height();
+
+    let mut paths = Paths {
+        width: width,
+        nodes: vec![Node {
+            point: origin,
+            entered: origin,
+            cost: 0,
+            previous: None,
+        }],
+        index: vec![None; size],
+    };
+    if let Some(slot) = slot(width, size, origin) {
+        paths.index[slot] = Some(0);
+    }
+
+    let mut frontier: Vec<Vec<usize>> = vec![vec![0]];
+    let mut cost = 0;
+    while cost < frontier.len() {
+        let bucket = mem::replace(&mut frontier[cost], vec![]);
+        for current in bucket {
+            let point = paths.nodes[current].point;
+            if paths.nodes[current].cost != cost {
+                // Found a cheaper way here after it was queued
+                continue;
+            }
+
+            for neighbor in level::neighbors(point) {
+                let step = match level.step(point, neighbor) {
+                    Some(step) => step,
+                    None => continue,
+                };
+                let new_cost = cost + step.cost;
+                if new_cost > budget {
+                    continue;
+                }
+                let slot = match slot(width, size, step.destination) {
+                    Some(slot) => slot,
+                    None => continue,
+                };
+
+                let node = Node {
+                    point: step.destination,
+                    entered: neighbor,
+                    cost: new_cost,
+                    previous: Some(current),
+                };
+                let queued = match paths.index[slot] {
+                    Some(existing) => {
+                        if paths.nodes[existing].cost <= new_cost {
+                            continue;
+                        }
+                        paths.nodes[existing] = node;
+                        existing
+                    }
+                    None => {
+                        paths.nodes.push(node);
+                        paths.index[slot] = Some(paths.nodes.len() - 1);
+                        paths.nodes.len() - 1
+                    }
+                };
+
+                while frontier.len() <= new_cost {
+                    frontier.push(vec![]);
+                }
+                frontier[new_cost].push(queued);
+            }
+        }
+        cost += 1;
+    }
+
+    paths
+}
+
+// Distance from every tile to the closest of a set of goals, for when
+// many starting points need to be compared against the same goals.
+pub struct DistanceField {
+    width: usize,
+    distances: Vec<Option<usize>>,
+}
+
+impl DistanceField {
+    pub fn distance(&self, point: Point) -> Option<usize> {
+        slot(self.width, self.distances.len(), point).and_then(|slot| self.distances[slot])
+    }
+}
+
+// Tiles a program could step from to end up on `point`, with the cost
+// of that step
+fn predecessors(level: &Level, point: Point) -> Vec<(Point, usize)> {
+    let mut entrances = vec![point];
+    if let Some(partner) = level.teleporter_exit(point) {
+        entrances.push(partner);
+    }
+
+    let mut result = vec![];
+    for entered in entrances {
+        for previous in level::neighbors(entered) {
+            if !level.passable(previous) {
+                continue;
+            }
+            if let Some(step) = level.step(previous, entered) {
+                if step.destination == point {
+                    result.push((previous, step.cost));
+                }
+            }
+        }
+    }
+    result
+}
+
+// The same search as `find`, run backwards from the goals
+pub fn distance_field(level: &Level, goals: &[Point]) -> DistanceField {
+    let width = level.width();
+    let size = width * level.height();
+
+    let mut field = DistanceField {
+        width: width,
+        distances: vec![None; size],
+    };
+
+    let mut frontier: Vec<Vec<Point>> = vec![vec![]];
+    for &goal in goals {
+        if let Some(slot) = slot(width, size, goal) {
+            if field.distances[slot].is_none() {
+                field.distances[slot] = Some(0);
+                frontier[0].push(goal);
+            }
+        }
+    }
+
+    let mut cost = 0;
+    while cost < frontier.len() {
+        let bucket = mem::replace(&mut frontier[cost], vec![]);
+        for point in bucket {
+            if field.distance(point) != Some(cost) {
+                continue;
+            }
+
+            for (previous, step_cost) in predecessors(level, point) {
+                let new_cost = cost + step_cost;
+                let slot = match slot(width, size, previous) {
+                    Some(slot) => slot,
+                    None => continue,
+                };
+                if let Some(existing) = field.distances[slot] {
+                    if existing <= new_cost {
+                        continue;
+                    }
+                }
+
+                field.distances[slot] = Some(new_cost);
+                while frontier.len() <= new_cost {
+                    frontier.push(vec![]);
+                }
+                frontier[new_cost].push(previous);
+            }
+        }
+        cost += 1;
+    }
+
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Point;
+    use engine::level::Level;
+    use engine::program::{Program, Team};
+    use super::{distance_field, find};
+
+    fn level(rows: &[&str]) -> Level {
+        let rows: Vec<String> = rows.iter().map(|&row| row.to_owned()).collect();
+        Level::new(&rows)
+    }
+
+    #[test]
+    fn slow_tiles_cost_two() {
+        let level = level(&[".~...", "....."]);
+        let paths = find(&level, Point::new(1, 1), 3);
+        assert_eq!(paths.cost_to(Point::new(2, 1)), Some(2));
+        assert_eq!(paths.cost_to(Point::new(3, 1)), Some(3));
+        assert_eq!(paths.path_to(Point::new(3, 1)), Some(vec![Point::new(2, 1), Point::new(3, 1)]));
+
+        let paths = find(&level, Point::new(1, 1), 1);
+        assert_eq!(paths.cost_to(Point::new(2, 1)), None);
+        assert_eq!(paths.reachable(), vec![Point::new(1, 2)]);
+    }
+
+    #[test]
+    fn paths_take_the_cheaper_way() {
+        let level = level(&[".~.", "..."]);
+        let paths = find(&level, Point::new(1, 1), 4);
+        assert_eq!(paths.cost_to(Point::new(3, 1)), Some(3));
+        assert_eq!(paths.cost_to(Point::new(3, 2)), Some(3));
+        assert_eq!(paths.path_to(Point::new(3, 2)), Some(vec![Point::new(1, 2), Point::new(2, 2), Point::new(3, 2)]));
+    }
+
+    #[test]
+    fn teleporters_move_to_their_partner() {
+        let level = level(&[".1  1."]);
+        let paths = find(&level, Point::new(1, 1), 2);
+        assert_eq!(paths.cost_to(Point::new(2, 1)), None);
+        assert_eq!(paths.cost_to(Point::new(5, 1)), Some(1));
+        assert_eq!(paths.path_to(Point::new(5, 1)), Some(vec![Point::new(2, 1)]));
+        assert_eq!(paths.path_to(Point::new(6, 1)), Some(vec![Point::new(2, 1), Point::new(6, 1)]));
+
+        let field = distance_field(&level, &[Point::new(6, 1)]);
+        assert_eq!(field.distance(Point::new(1, 1)), Some(2));
+    }
+
+    #[test]
+    fn blocked_teleporters_are_stepped_on() {
+        let mut level = level(&[".1  1."]);
+        level.add_program(Program::new(Team::Enemy, Point::new(5, 1), "Patrol"));
+        let paths = find(&level, Point::new(1, 1), 2);
+        assert_eq!(paths.cost_to(Point::new(2, 1)), Some(1));
+        assert_eq!(paths.cost_to(Point::new(6, 1)), None);
+    }
+}
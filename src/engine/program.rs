@@ -0,0 +1,688 @@
+use std::sync::Arc;
+
+use engine::Point;
+use engine::ai::Behavior;
+use engine::level::{CellContents, Level, Step};
+use engine::tile::Tile;
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum AbilityKind {
+    Destroy { damage: usize },
+    // Turns an empty tile into void
+    DeleteSector,
+    // Turns a void tile into floor
+    CreateSector,
+    // Regrows a friendly program's tail
+    Repair { amount: usize },
+    // Buffs an ally or debuffs an enemy, depending on the effect
+    Status(StatusEffect),
+}
+
+// The area an ability hits, relative to the tile it is aimed at
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Shape {
+    Single,
+    // Every tile from the caster out to the ability's range, in the
+    // direction of the target; can only be aimed along a row or column
+    Line,
+    // The target plus the given number of tiles in each direction
+    Cross(usize),
+    // Widens by one tile on each side per step away from the caster
+    Cone,
+    // Every tile within the given distance of the target
+    Splash(usize),
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct Ability {
+    pub kind: AbilityKind,
+    pub range: usize,
+    pub shape: Shape,
+    // Whether void between the caster and the target blocks the ability
+    pub line_of_sight: bool,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum StatusEffect {
+    Damage(usize),
+    Repair(usize),
+    Haste { amount: usize, turns: usize },
+    Slowed { amount: usize, turns: usize },
+    Stun { turns: usize },
+    Shield { amount: usize, turns: usize },
+}
+
+impl StatusEffect {
+    pub fn beneficial(&self) -> bool {
+        match *self {
+            StatusEffect::Repair(_) | StatusEffect::Haste { .. } | StatusEffect::Shield { .. } => true,
+            StatusEffect::Damage(_) | StatusEffect::Slowed { .. } | StatusEffect::Stun { .. } => false,
+        }
+    }
+
+    // Effects that last a number of turns, rather than playing out
+    // over a few ticks
+    pub fn turns(&self) -> Option<usize> {
+        match *self {
+            StatusEffect::Haste { turns, .. } |
+            StatusEffect::Slowed { turns, .. } |
+            StatusEffect::Stun { turns } |
+            StatusEffect::Shield { turns, .. } => Some(turns),
+            StatusEffect::Damage(_) | StatusEffect::Repair(_) => None,
+        }
+    }
+
+    fn with_turns(&self, turns: usize) -> StatusEffect {
+        match *self {
+            StatusEffect::Haste { amount, .. } => StatusEffect::Haste { amount: amount, turns: turns },
+            StatusEffect::Slowed { amount, .. } => StatusEffect::Slowed { amount: amount, turns: turns },
+            StatusEffect::Stun { .. } => StatusEffect::Stun { turns: turns },
+            StatusEffect::Shield { amount, .. } => StatusEffect::Shield { amount: amount, turns: turns },
+            effect => effect,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            StatusEffect::Damage(amount) => format!("Damage 0x{:x}", amount),
+            StatusEffect::Repair(amount) => format!("Repair 0x{:x}", amount),
+            StatusEffect::Haste { amount, turns } => format!("Haste +{} ({}t)", amount, turns),
+            StatusEffect::Slowed { amount, turns } => format!("Slowed -{} ({}t)", amount, turns),
+            StatusEffect::Stun { turns } => format!("Stunned ({}t)", turns),
+            StatusEffect::Shield { amount, turns } => format!("Shield {} ({}t)", amount, turns),
+        }
+    }
+}
+
+impl Ability {
+    pub fn new(kind: AbilityKind, range: usize) -> Ability {
+        Ability {
+            kind: kind,
+            range: range,
+            shape: Shape::Single,
+            line_of_sight: false,
+        }
+    }
+
+    pub fn destroy(damage: usize, range: usize) -> Ability {
+        Ability::new(AbilityKind::Destroy { damage: damage }, range)
+    }
+
+    pub fn delete_sector(range: usize) -> Ability {
+        Ability::new(AbilityKind::DeleteSector, range)
+    }
+
+    pub fn create_sector(range: usize) -> Ability {
+        Ability::new(AbilityKind::CreateSector, range)
+    }
+
+    pub fn repair(amount: usize, range: usize) -> Ability {
+        Ability::new(AbilityKind::Repair { amount: amount }, range)
+    }
+
+    pub fn status(effect: StatusEffect, range: usize) -> Ability {
+        Ability::new(AbilityKind::Status(effect), range)
+    }
+
+    pub fn shape(mut self, shape: Shape) -> Ability {
+        self.shape = shape;
+        self
+    }
+
+    pub fn line_of_sight(mut self, required: bool) -> Ability {
+        self.line_of_sight = required;
+        self
+    }
+
+    pub fn describe(&self) -> String {
+        let kind = match self.kind {
+            AbilityKind::Destroy { damage } => format!("Damage: 0x{:x}", damage),
+            AbilityKind::DeleteSector => "Delete sector.".to_owned(),
+            AbilityKind::CreateSector => "Create sector.".to_owned(),
+            AbilityKind::Repair { amount } => format!("Repair: 0x{:x}", amount),
+            AbilityKind::Status(effect) => effect.describe(),
+        };
+        let shape = match self.shape {
+            Shape::Single => String::new(),
+            Shape::Line => " Line".to_owned(),
+            Shape::Cross(size) => format!(" Cross: 0x{:x}", size),
+            Shape::Cone => " Cone".to_owned(),
+            Shape::Splash(radius) => format!(" Splash: 0x{:x}", radius),
+        };
+        let sight = if self.line_of_sight { " LOS" } else { "" };
+        format!("{} Range: 0x{:x}{}{}", kind, self.range, shape, sight)
+    }
+
+    // Whether the ability is meant to be used on opposing programs
+    pub fn offensive(&self) -> bool {
+        match self.kind {
+            AbilityKind::Destroy { .. } => true,
+            AbilityKind::Status(effect) => !effect.beneficial(),
+            AbilityKind::DeleteSector | AbilityKind::CreateSector | AbilityKind::Repair { .. } => false,
+        }
+    }
+
+    // How a valid target is marked on the map
+    pub fn target_glyph(&self) -> char {
+        match self.kind {
+            AbilityKind::Destroy { .. } => 'X',
+            AbilityKind::DeleteSector => '-',
+            AbilityKind::CreateSector => '#',
+            AbilityKind::Repair { .. } => '+',
+            AbilityKind::Status(_) => '*',
+        }
+    }
+
+    // Tiles the ability can be aimed at
+    pub fn reachable_tiles(&self, center: Point) -> Vec<Point> {
+        let mut result = vec![];
+        let range = self.range as isize;
+        for dx in -range..range + 1 {
+            for dy in -range..range + 1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                if self.shape == Shape::Line && dx != 0 && dy != 0 {
+                    continue;
+                }
+                if dx.abs() + dy.abs() <= range {
+                    if let Some(p) = offset(center, dx, dy) {
+                        result.push(p);
+                    }
+                }
+            }
+        }
+
+        return result;
+    }
+
+    // Tiles the ability can be aimed at, minus those out of sight
+    pub fn visible_tiles(&self, level: &Level, center: Point) -> Vec<Point> {
+        self.reachable_tiles(center).into_iter().filter(|&p| {
+            !self.line_of_sight || level.line_of_sight(center, p)
+        }).collect()
+    }
+
+    // Tiles hit when cast from `origin` at `target`
+    pub fn area(&self, origin: Point, target: Point) -> Vec<Point> {
+        let mut result = vec![];
+        let range = self.range as isize;
+        match self.shape {
+            Shape::Single => result.push(target),
+            Shape::Line => {
+                let (dx, dy) = direction_towards(origin, target);
+                for step in 1..range + 1 {
+                    if let Some(p) = offset(origin, dx * step, dy * step) {
+                        result.push(p);
+                    }
+                }
+            }
+            Shape::Cross(size) => {
+                result.push(target);
+                for step in 1..size as isize + 1 {
+                    for &(dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+                        if let Some(p) = offset(target, dx * step, dy * step) {
+                            result.push(p);
+                        }
+                    }
+                }
+            }
+            Shape::Cone => {
+                let (dx, dy) = direction_towards(origin, target);
+                for step in 1..range + 1 {
+                    for side in -(step - 1)..step {
+                        // Spread perpendicular to the direction of the cone
+                        if let Some(p) = offset(origin, dx * step + dy * side, dy * step + dx * side) {
+                            result.push(p);
+                        }
+                    }
+                }
+            }
+            Shape::Splash(radius) => {
+                let radius = radius as isize;
+                for dx in -radius..radius + 1 {
+                    for dy in -radius..radius + 1 {
+                        if dx.abs() + dy.abs() <= radius {
+                            if let Some(p) = offset(target, dx, dy) {
+                                result.push(p);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    // Whether the ability does anything to a single tile
+    pub fn affects(&self, level: &Level, caster: &Program, tile: Point) -> bool {
+        match self.kind {
+            AbilityKind::Destroy { .. } => {
+                match level.contents_of(tile) {
+                    CellContents::Program(id) => level.programs[&id].team != caster.team,
+                    _ => false,
+                }
+            }
+            AbilityKind::DeleteSector => {
+                match level.contents_of(tile) {
+                    CellContents::Empty => true,
+                    _ => false,
+                }
+            }
+            AbilityKind::CreateSector => {
+                level.in_bounds(tile) && level.tile(tile) == Tile::Void
+            }
+            AbilityKind::Repair { .. } => {
+                match level.contents_of(tile) {
+                    CellContents::Program(id) => {
+                        let program = &level.programs[&id];
+                        program.team == caster.team && program.can_grow()
+                    }
+                    _ => false,
+                }
+            }
+            AbilityKind::Status(effect) => {
+                match level.contents_of(tile) {
+                    CellContents::Program(id) => {
+                        (level.programs[&id].team == caster.team) == effect.beneficial()
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    // The tiles in the area that the ability actually does something to
+    pub fn affected_tiles(&self, level: &Level, caster: &Program, target: Point) -> Vec<Point> {
+        self.area(caster.position, target).into_iter().filter(|&p| {
+            self.affects(level, caster, p)
+        }).collect()
+    }
+
+    pub fn valid_target(&self, level: &Level, caster: &Program, target: Point) -> bool {
+        if !self.reachable_tiles(caster.position).contains(&target) {
+            return false;
+        }
+        if self.line_of_sight && !level.line_of_sight(caster.position, target) {
+            return false;
+        }
+        self.affected_tiles(level, caster, target).len() > 0
+    }
+
+    pub fn targets(&self, level: &Level, caster: &Program) -> Vec<Point> {
+        self.visible_tiles(level, caster.position).into_iter().filter(|&p| {
+            self.valid_target(level, caster, p)
+        }).collect()
+    }
+
+    // Programs are hit once no matter how many of their tiles are in
+    // the area; friendly and hostile programs are told apart the same
+    // way as for single targets.
+    pub fn apply(&self, level: &mut Level, caster: ProgramId, target: Point) {
+        let tiles = match level.programs.get(&caster) {
+            Some(caster) => self.affected_tiles(level, caster, target),
+            None => return,
+        };
+
+        let mut hit: Vec<ProgramId> = vec![];
+        for tile in tiles {
+            match self.kind {
+                AbilityKind::DeleteSector => level.set_tile(tile, Tile::Void),
+                AbilityKind::CreateSector => level.set_tile(tile, Tile::Floor),
+                AbilityKind::Destroy { .. } | AbilityKind::Repair { .. } | AbilityKind::Status(_) => {
+                    if let CellContents::Program(id) = level.contents_of(tile) {
+                        if hit.contains(&id) {
+                            continue;
+                        }
+                        hit.push(id);
+
+                        let program = level.programs.get_mut(&id).unwrap();
+                        match self.kind {
+                            AbilityKind::Destroy { damage } => program.add_damage(damage),
+                            AbilityKind::Repair { amount } => program.add_repair(amount),
+                            AbilityKind::Status(effect) => program.status_effects.push(effect),
+                            AbilityKind::DeleteSector | AbilityKind::CreateSector => unreachable!(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn offset(point: Point, dx: isize, dy: isize) -> Option<Point> {
+    let (x, y) = (point.x as isize + dx, point.y as isize + dy);
+    // Guard at map edges
+    if x <= 0 || y <= 0 {
+        None
+    }
+    else {
+        Some(Point::new(x as u16, y as u16))
+    }
+}
+
+// Unit step along the dominant axis from one point towards another
+fn direction_towards(from: Point, to: Point) -> (isize, isize) {
+    let dx = to.x as isize - from.x as isize;
+    let dy = to.y as isize - from.y as isize;
+    if dx.abs() >= dy.abs() {
+        (dx.signum(), 0)
+    }
+    else {
+        (0, dy.signum())
+    }
+}
+
+#[derive(Clone,Copy,Debug)]
+pub struct ProgramTurnState {
+    pub moves_made: usize,
+    pub ability_used: bool,
+}
+
+// Identifies a program on a level for as long as it stays there
+#[derive(Clone,Copy,Debug,Eq,PartialEq,Ord,PartialOrd,Hash)]
+pub struct ProgramId(pub usize);
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Team {
+    Player,
+    Enemy,
+}
+
+#[derive(Clone,Debug)]
+pub struct Program {
+    pub team: Team,
+    pub position: Point,
+    tail: Vec<Point>,
+    pub name: String,
+    pub abilities: Vec<(String, Ability)>,
+    pub max_tail: usize,
+    pub max_moves: usize,
+    pub turn_state: ProgramTurnState,
+    pub status_effects: Vec<StatusEffect>,
+    // How the AI moves the program; enemies without one hunt the player
+    pub behavior: Option<Arc<Behavior>>,
+    // Where the program was loaded onto the level
+    pub home: Point,
+}
+
+impl ProgramTurnState {
+    fn new() -> ProgramTurnState {
+        ProgramTurnState {
+            moves_made: 0,
+            ability_used: false,
+        }
+    }
+}
+
+impl Program {
+    pub fn new(team: Team, position: Point, name: &str) -> Program {
+        Program {
+            team: team,
+            position: position,
+            tail: vec![],
+            name: name.to_owned(),
+            abilities: vec![],
+            max_tail: 4,
+            max_moves: 3,
+            turn_state: ProgramTurnState::new(),
+            status_effects: vec![],
+            behavior: None,
+            home: position,
+        }
+    }
+
+    pub fn begin_turn(&mut self) {
+        self.turn_state.moves_made = 0;
+        self.turn_state.ability_used = false;
+    }
+
+    // Timed effects count down at the end of each of the program's turns,
+    // so one lasting N turns covers N of them, the current one included
+    pub fn end_turn(&mut self) {
+        for effect in self.status_effects.iter_mut() {
+            if let Some(turns) = effect.turns() {
+                *effect = effect.with_turns(turns.saturating_sub(1));
+            }
+        }
+        self.status_effects.retain(|effect| effect.turns() != Some(0));
+    }
+
+    pub fn stunned(&self) -> bool {
+        self.status_effects.iter().any(|effect| {
+            match *effect {
+                StatusEffect::Stun { .. } => true,
+                _ => false,
+            }
+        })
+    }
+
+    // Moves per turn after buffs and debuffs
+    pub fn moves_allowed(&self) -> usize {
+        if self.stunned() {
+            return 0;
+        }
+
+        let mut moves = self.max_moves;
+        for effect in self.status_effects.iter() {
+            match *effect {
+                StatusEffect::Haste { amount, .. } => moves += amount,
+                StatusEffect::Slowed { amount, .. } => moves = moves.saturating_sub(amount),
+                _ => {}
+            }
+        }
+        moves
+    }
+
+    pub fn can_move(&self) -> bool {
+        self.moves_left() > 0
+    }
+
+    pub fn moves_left(&self) -> usize {
+        self.moves_allowed().saturating_sub(self.turn_state.moves_made)
+    }
+
+    pub fn can_use_ability(&self) -> bool {
+        !self.turn_state.ability_used && !self.stunned()
+    }
+
+    pub fn move_to(&mut self, step: Step) {
+        if self.moves_left() < step.cost {
+            return;
+        }
+
+        self.turn_state.moves_made += step.cost;
+
+        self.tail.push(self.position);
+        if self.tail.len() >= self.max_tail {
+            self.tail.remove(0);
+        }
+        self.position = step.destination;
+    }
+
+    pub fn add_damage(&mut self, damage: usize) {
+        let mut damage = damage;
+        for effect in self.status_effects.iter_mut() {
+            if let StatusEffect::Shield { amount, turns } = *effect {
+                let absorbed = ::std::cmp::min(amount, damage);
+                damage -= absorbed;
+                *effect = StatusEffect::Shield { amount: amount - absorbed, turns: turns };
+            }
+        }
+        self.status_effects.retain(|effect| {
+            match *effect {
+                StatusEffect::Shield { amount: 0, .. } => false,
+                _ => true,
+            }
+        });
+        if damage == 0 {
+            return;
+        }
+
+        for effect in self.status_effects.iter_mut() {
+            if let StatusEffect::Damage(x) = *effect {
+                *effect = StatusEffect::Damage(x + damage);
+                return;
+            }
+        }
+
+        self.status_effects.push(StatusEffect::Damage(damage));
+    }
+
+    pub fn add_repair(&mut self, amount: usize) {
+        for effect in self.status_effects.iter_mut() {
+            if let StatusEffect::Repair(x) = *effect {
+                *effect = StatusEffect::Repair(x + amount);
+                return;
+            }
+        }
+
+        self.status_effects.push(StatusEffect::Repair(amount));
+    }
+
+    pub fn damage(&mut self) -> bool {
+        if self.tail.len() > 0 {
+            self.tail.remove(0);
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    // The tail, from its end up to the head
+    pub fn tail(&self) -> &[Point] {
+        &self.tail
+    }
+
+    pub fn set_tail(&mut self, tail: Vec<Point>) {
+        self.tail = tail;
+    }
+
+    // Every tile the program occupies, head last
+    pub fn tiles(&self) -> Vec<Point> {
+        let mut tiles = self.tail.clone();
+        tiles.push(self.position);
+        tiles
+    }
+
+    // Number of tiles the program occupies
+    pub fn size(&self) -> usize {
+        self.tail.len() + 1
+    }
+
+    pub fn can_grow(&self) -> bool {
+        self.size() < self.max_tail
+    }
+
+    // The segment new tail pieces attach to
+    pub fn tail_end(&self) -> Point {
+        self.tail.first().cloned().unwrap_or(self.position)
+    }
+
+    pub fn grow(&mut self, point: Point) {
+        if self.can_grow() {
+            self.tail.insert(0, point);
+        }
+    }
+
+    // How each tile of the program is drawn: box-drawing pieces for the
+    // tail and the first letter of its name for the head
+    pub fn glyphs(&self) -> Vec<(Point, char)> {
+        let mut result = vec![];
+
+        let mut prev: Option<Point> = None;
+        let mut cells = self.tail.to_vec();
+        cells.push(self.position);
+        for w in cells.windows(2) {
+            let (cur, next) = (w[0], w[1]);
+            let (x1, y1) = (cur.x as i32, cur.y as i32);
+            let (x2, y2) = (next.x as i32, next.y as i32);
+            let dx = x2 - x1;
+            let dy = y2 - y1;
+
+            let pdx = prev.map(|p| x1 - p.x as i32);
+            let pdy = prev.map(|p| y1 - p.y as i32);
+
+            let c = match (pdx, pdy, dx, dy) {
+                (None, None, 1, 0) | (None, None, -1, 0) | (Some(1), _, 1, 0) | (Some(-1), _, -1, 0) => '═',
+                (None, None, 0, 1) | (None, None, 0, -1) | (_, Some(1), 0, 1) | (_, Some(-1), 0, -1) => '║',
+                (Some(1), _, 0, 1) | (_, Some(-1), -1, 0) => '╗',
+                (Some(1), _, 0, -1) | (_, Some(1), -1, 0) => '╝',
+                (Some(-1), _, 0, -1) | (_, Some(1), 1, 0) => '╚',
+                (Some(-1), _, 0, 1) | (_, Some(-1), 1, 0) => '╔',
+                _ => '+',
+            };
+            result.push((cur, c));
+            prev = Some(cur);
+        }
+
+        result.push((self.position, self.name.chars().next().unwrap()));
+
+        result
+    }
+
+    pub fn intersects(&self, point: Point) -> bool {
+        for t in self.tail.iter() {
+            if *t == point {
+                return true;
+            }
+        }
+        self.position == point
+    }
+}
+
+pub struct ProgramBuilder {
+    name: String,
+    abilities: Vec<(String, Ability)>,
+    max_tail: usize,
+    max_moves: usize,
+    behavior: Option<Arc<Behavior>>,
+}
+
+impl ProgramBuilder {
+    pub fn new<S: Into<String>>(name: S) -> ProgramBuilder {
+        ProgramBuilder {
+            name: name.into(),
+            abilities: Vec::new(),
+            max_tail: 1,
+            max_moves: 1,
+            behavior: None,
+        }
+    }
+
+    pub fn max_tail(mut self, size: usize) -> ProgramBuilder {
+        self.max_tail = size;
+        self
+    }
+
+    pub fn max_moves(mut self, moves: usize) -> ProgramBuilder {
+        self.max_moves = moves;
+        self
+    }
+
+    pub fn ability<S: Into<String>>(mut self, name: S, ability: Ability) -> ProgramBuilder {
+        self.abilities.push((name.into(), ability));
+        self
+    }
+
+    pub fn name<S: Into<String>>(mut self, name: S) -> ProgramBuilder {
+        self.name = name.into();
+        self
+    }
+
+    pub fn behavior<B: Behavior + 'static>(mut self, behavior: B) -> ProgramBuilder {
+        self.behavior = Some(Arc::new(behavior));
+        self
+    }
+
+    pub fn instance(&self, team: Team) -> Program {
+        let mut p = Program::new(team, Point::new(0, 0), &self.name);
+        p.max_tail = self.max_tail;
+        p.max_moves = self.max_moves;
+        p.abilities.extend_from_slice(&self.abilities);
+        p.behavior = self.behavior.clone();
+        p
+    }
+}
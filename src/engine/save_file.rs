@@ -0,0 +1,547 @@
+use engine::Point;
+use engine::ai::Difficulty;
+use engine::level::Level;
+use engine::level_file::{self, ParseError};
+use engine::program::{Ability, AbilityKind, Program, ProgramId, Shape, StatusEffect, Team};
+use engine::tile::Tile;
+
+// Saved missions are plain text, one `key: value` per line:
+//
+//     version: 1
+//     level: 0
+//     phase: player
+//     difficulty: NORMAL
+//     turn: player
+//     title: Perimeter
+//     row: |....o...|
+//
+//     program: 3
+//     team: enemy
+//     name: Patrol
+//     position: 5,2
+//     tail: 5,4 5,3
+//     ability: Delete = destroy:4 range:1 shape:single sight:no
+//     status: slowed:2/1
+//
+//     roster:
+//     name: Hack 1
+//
+// A `program:` line starts a program on the level, with its ID; a
+// `roster:` line starts one of the player's programs. The keys after it,
+// up to the next one, describe that program. Rows are wrapped in `|` so
+// that void at either end survives. Bump VERSION whenever the format
+// changes; older saves are refused rather than misread.
+
+pub const VERSION: usize = 1;
+
+// Which part of the mission the save was made in
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Phase {
+    // Still loading programs onto uplinks
+    Setup,
+    PlayerTurn,
+}
+
+pub struct SaveFile {
+    pub level_index: usize,
+    pub phase: Phase,
+    pub difficulty: Difficulty,
+    pub level: Level,
+    // The player's programs, deployed or not
+    pub roster: Vec<Program>,
+}
+
+// One `key: value` line, with where it came from for errors
+pub struct Entry<'a> {
+    pub line: usize,
+    pub column: usize,
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+enum Block {
+    Header,
+    Program(ProgramId, Program),
+    Roster(Program),
+}
+
+fn team_name(team: Team) -> &'static str {
+    match team {
+        Team::Player => "player",
+        Team::Enemy => "enemy",
+    }
+}
+
+fn parse_team(value: &str) -> Option<Team> {
+    match value {
+        "player" => Some(Team::Player),
+        "enemy" => Some(Team::Enemy),
+        _ => None,
+    }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_point(value: &str) -> Option<Point> {
+    let mut parts = value.split(',');
+    match (parts.next().and_then(|x| x.parse().ok()), parts.next().and_then(|y| y.parse().ok()), parts.next()) {
+        (Some(x), Some(y), None) => Some(Point::new(x, y)),
+        _ => None,
+    }
+}
+
+// Splits `name:argument` into its halves
+fn split_token(token: &str) -> (&str, Option<&str>) {
+    match token.find(':') {
+        Some(split) => (&token[..split], Some(&token[split + 1..])),
+        None => (token, None),
+    }
+}
+
+// Splits `amount/turns`
+fn parse_pair(value: Option<&str>) -> Option<(usize, usize)> {
+    let value = match value {
+        Some(value) => value,
+        None => return None,
+    };
+    let mut parts = value.split('/');
+    match (parts.next().and_then(|a| a.parse().ok()), parts.next().and_then(|b| b.parse().ok()), parts.next()) {
+        (Some(a), Some(b), None) => Some((a, b)),
+        _ => None,
+    }
+}
+
+fn effect_token(effect: &StatusEffect) -> String {
+    match *effect {
+        StatusEffect::Damage(amount) => format!("damage:{}", amount),
+        StatusEffect::Repair(amount) => format!("repair:{}", amount),
+        StatusEffect::Haste { amount, turns } => format!("haste:{}/{}", amount, turns),
+        StatusEffect::Slowed { amount, turns } => format!("slowed:{}/{}", amount, turns),
+        StatusEffect::Stun { turns } => format!("stun:{}", turns),
+        StatusEffect::Shield { amount, turns } => format!("shield:{}/{}", amount, turns),
+    }
+}
+
+fn parse_effect(token: &str) -> Option<StatusEffect> {
+    let (name, argument) = split_token(token);
+    let number = argument.and_then(|a| a.parse().ok());
+    match name {
+        "damage" => number.map(StatusEffect::Damage),
+        "repair" => number.map(StatusEffect::Repair),
+        "haste" => parse_pair(argument).map(|(amount, turns)| StatusEffect::Haste { amount: amount, turns: turns }),
+        "slowed" => parse_pair(argument).map(|(amount, turns)| StatusEffect::Slowed { amount: amount, turns: turns }),
+        "stun" => number.map(|turns| StatusEffect::Stun { turns: turns }),
+        "shield" => parse_pair(argument).map(|(amount, turns)| StatusEffect::Shield { amount: amount, turns: turns }),
+        _ => None,
+    }
+}
+
+fn kind_token(kind: &AbilityKind) -> String {
+    match *kind {
+        AbilityKind::Destroy { damage } => format!("destroy:{}", damage),
+        AbilityKind::DeleteSector => "delete".to_owned(),
+        AbilityKind::CreateSector => "create".to_owned(),
+        AbilityKind::Repair { amount } => format!("repair:{}", amount),
+        // Status effects have their own colon, so the rest is the effect
+        AbilityKind::Status(ref effect) => format!("status:{}", effect_token(effect)),
+    }
+}
+
+fn parse_kind(token: &str) -> Option<AbilityKind> {
+    let (name, argument) = split_token(token);
+    let number = argument.and_then(|a| a.parse().ok());
+    match name {
+        "destroy" => number.map(|damage| AbilityKind::Destroy { damage: damage }),
+        "delete" => Some(AbilityKind::DeleteSector),
+        "create" => Some(AbilityKind::CreateSector),
+        "repair" => number.map(|amount| AbilityKind::Repair { amount: amount }),
+        "status" => argument.and_then(parse_effect).map(AbilityKind::Status),
+        _ => None,
+    }
+}
+
+fn shape_token(shape: &Shape) -> String {
+    match *shape {
+        Shape::Single => "single".to_owned(),
+        Shape::Line => "line".to_owned(),
+        Shape::Cross(size) => format!("cross:{}", size),
+        Shape::Cone => "cone".to_owned(),
+        Shape::Splash(size) => format!("splash:{}", size),
+    }
+}
+
+fn parse_shape(token: &str) -> Option<Shape> {
+    let (name, argument) = split_token(token);
+    let number = argument.and_then(|a| a.parse().ok());
+    match name {
+        "single" => Some(Shape::Single),
+        "line" => Some(Shape::Line),
+        "cross" => number.map(Shape::Cross),
+        "cone" => Some(Shape::Cone),
+        "splash" => number.map(Shape::Splash),
+        _ => None,
+    }
+}
+
+fn ability_line(name: &str, ability: &Ability) -> String {
+    format!("{} = {} range:{} shape:{} sight:{}",
+            name, kind_token(&ability.kind), ability.range, shape_token(&ability.shape),
+            if ability.line_of_sight { "yes" } else { "no" })
+}
+
+fn parse_ability(value: &str) -> Option<(String, Ability)> {
+    let split = match value.rfind('=') {
+        Some(split) => split,
+        None => return None,
+    };
+    let name = value[..split].trim();
+    let mut tokens = value[split + 1..].split_whitespace();
+
+    let mut ability = match tokens.next().and_then(parse_kind) {
+        Some(kind) => Ability::new(kind, 0),
+        None => return None,
+    };
+    for token in tokens {
+        match split_token(token) {
+            ("range", Some(range)) => match range.parse() {
+                Ok(range) => ability.range = range,
+                Err(_) => return None,
+            },
+            ("shape", Some(shape)) => match parse_shape(shape) {
+                Some(shape) => ability.shape = shape,
+                None => return None,
+            },
+            ("sight", Some(sight)) => match parse_flag(sight) {
+                Some(sight) => ability.line_of_sight = sight,
+                None => return None,
+            },
+            _ => return None,
+        }
+    }
+    Some((name.to_owned(), ability))
+}
+
+pub fn write_program(out: &mut String, program: &Program) {
+    out.push_str(&format!("team: {}\n", team_name(program.team)));
+    out.push_str(&format!("name: {}\n", program.name));
+    out.push_str(&format!("position: {},{}\n", program.position.x, program.position.y));
+    out.push_str(&format!("home: {},{}\n", program.home.x, program.home.y));
+    let tail: Vec<String> = program.tail().iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+    out.push_str(&format!("tail: {}\n", tail.join(" ")));
+    out.push_str(&format!("max_tail: {}\n", program.max_tail));
+    out.push_str(&format!("max_moves: {}\n", program.max_moves));
+    out.push_str(&format!("moves_made: {}\n", program.turn_state.moves_made));
+    out.push_str(&format!("ability_used: {}\n", if program.turn_state.ability_used { "yes" } else { "no" }));
+    for &(ref name, ref ability) in program.abilities.iter() {
+        out.push_str(&format!("ability: {}\n", ability_line(name, ability)));
+    }
+    for effect in program.status_effects.iter() {
+        out.push_str(&format!("status: {}\n", effect_token(effect)));
+    }
+}
+
+pub fn write(save: &SaveFile) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("version: {}\n", VERSION));
+    out.push_str(&format!("level: {}\n", save.level_index));
+    out.push_str(&format!("phase: {}\n", match save.phase {
+        Phase::Setup => "setup",
+        Phase::PlayerTurn => "player",
+    }));
+    out.push_str(&format!("difficulty: {}\n", save.difficulty.name()));
+    out.push_str(&format!("turn: {}\n", team_name(save.level.turn)));
+    out.push_str(&format!("title: {}\n", save.level.title));
+    for row in save.level.layout.iter() {
+        let row: String = row.iter().map(Tile::to_char).collect();
+        out.push_str(&format!("row: |{}|\n", row));
+    }
+
+    for (&ProgramId(id), program) in save.level.programs.iter() {
+        out.push_str(&format!("\nprogram: {}\n", id));
+        write_program(&mut out, program);
+    }
+    for program in save.roster.iter() {
+        out.push_str("\nroster:\n");
+        write_program(&mut out, program);
+    }
+    out
+}
+
+// Sets one field of a program from its line; false if the key is unknown
+// or the value doesn't parse
+pub fn read_program_key(program: &mut Program, key: &str, value: &str) -> bool {
+    match key {
+        "team" => parse_team(value).map(|team| program.team = team).is_some(),
+        "name" => {
+            program.name = value.to_owned();
+            !value.is_empty()
+        }
+        "position" => parse_point(value).map(|p| program.position = p).is_some(),
+        "home" => parse_point(value).map(|p| program.home = p).is_some(),
+        "tail" => {
+            let tail: Vec<Option<Point>> = value.split_whitespace().map(parse_point).collect();
+            if tail.iter().any(Option::is_none) {
+                return false;
+            }
+            program.set_tail(tail.into_iter().map(Option::unwrap).collect());
+            true
+        }
+        "max_tail" => value.parse().map(|n| program.max_tail = n).is_ok(),
+        "max_moves" => value.parse().map(|n| program.max_moves = n).is_ok(),
+        "moves_made" => value.parse().map(|n| program.turn_state.moves_made = n).is_ok(),
+        "ability_used" => parse_flag(value).map(|used| program.turn_state.ability_used = used).is_some(),
+        "ability" => parse_ability(value).map(|ability| program.abilities.push(ability)).is_some(),
+        "status" => parse_effect(value).map(|effect| program.status_effects.push(effect)).is_some(),
+        _ => false,
+    }
+}
+
+pub fn blank_program() -> Program {
+    Program::new(Team::Player, Point::new(0, 0), "")
+}
+
+// Programs come back without a behavior; the caller looks them up again
+impl<'a> Entry<'a> {
+    pub fn error<S: Into<String>>(&self, message: S) -> ParseError {
+        ParseError::new(self.line, self.column, message)
+    }
+
+    pub fn bad_value(&self) -> ParseError {
+        self.error(format!("bad value for '{}'", self.key))
+    }
+
+    pub fn unknown_key(&self) -> ParseError {
+        self.error(format!("unknown key '{}'", self.key))
+    }
+}
+
+// Splits a file in this format into its `key: value` lines, skipping
+// blanks and `#` comments. The first key must be `version`, matching
+// `version`; it is checked here and left out. `kind` names the file in
+// errors.
+pub fn read_entries<'a>(source: &'a str, kind: &str, version: usize) -> Result<Vec<Entry<'a>>, ParseError> {
+    let mut entries = vec![];
+    for (index, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let column = level_file::column_of(line, trimmed);
+        let split = match trimmed.find(':') {
+            Some(split) => split,
+            None => return Err(ParseError::new(index + 1, column, "expected 'key: value'")),
+        };
+        entries.push(Entry {
+            line: index + 1,
+            column: column,
+            key: trimmed[..split].trim(),
+            value: trimmed[split + 1..].trim(),
+        });
+    }
+
+    let first = match entries.first() {
+        Some(first) => first,
+        None => return Err(ParseError::new(source.lines().count() + 1, 1, "missing 'version'")),
+    };
+    if first.key != "version" {
+        return Err(first.error("missing 'version' before the first key"));
+    }
+    match first.value.parse::<usize>() {
+        Ok(found) if found == version => {}
+        Ok(found) => return Err(first.error(format!("unsupported {} version {}, expected {}", kind, found, version))),
+        Err(_) => return Err(first.bad_value()),
+    }
+    entries.remove(0);
+    Ok(entries)
+}
+
+pub fn parse(source: &str) -> Result<SaveFile, ParseError> {
+    let mut level_index = None;
+    let mut phase = None;
+    let mut difficulty = None;
+    let mut turn = None;
+    let mut title = String::new();
+    let mut rows: Vec<String> = vec![];
+    let mut programs: Vec<(ProgramId, Program)> = vec![];
+    let mut roster = vec![];
+    let mut block = Block::Header;
+
+    for entry in try!(read_entries(source, "save", VERSION)) {
+        let (key, value) = (entry.key, entry.value);
+        let bad_value = || entry.bad_value();
+
+        let next = match key {
+            "program" => match value.parse() {
+                Ok(id) => Some(Block::Program(ProgramId(id), blank_program())),
+                Err(_) => return Err(bad_value()),
+            },
+            "roster" => Some(Block::Roster(blank_program())),
+            _ => None,
+        };
+        if let Some(next) = next {
+            match ::std::mem::replace(&mut block, next) {
+                Block::Header => {}
+                Block::Program(id, program) => programs.push((id, program)),
+                Block::Roster(program) => roster.push(program),
+            }
+            continue;
+        }
+
+        match block {
+            Block::Header => match key {
+                "level" => level_index = Some(try!(value.parse().map_err(|_| bad_value()))),
+                "phase" => phase = Some(match value {
+                    "setup" => Phase::Setup,
+                    "player" => Phase::PlayerTurn,
+                    _ => return Err(bad_value()),
+                }),
+                "difficulty" => {
+                    let all = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+                    difficulty = Some(try!(all.iter().cloned().find(|d| d.name() == value).ok_or_else(&bad_value)));
+                }
+                "turn" => turn = Some(try!(parse_team(value).ok_or_else(&bad_value))),
+                "title" => title = value.to_owned(),
+                "row" => {
+                    if value.len() < 2 || !value.starts_with('|') || !value.ends_with('|') {
+                        return Err(bad_value());
+                    }
+                    let row = &value[1..value.len() - 1];
+                    if let Some(c) = row.chars().find(|&c| !level_file::is_terrain(c)) {
+                        return Err(entry.error(format!("unknown glyph '{}'", c)));
+                    }
+                    rows.push(row.to_owned());
+                }
+                _ => return Err(entry.unknown_key()),
+            },
+            Block::Program(_, ref mut program) | Block::Roster(ref mut program) => {
+                if !read_program_key(program, key, value) {
+                    return Err(bad_value());
+                }
+            }
+        }
+    }
+
+    match block {
+        Block::Header => {}
+        Block::Program(id, program) => programs.push((id, program)),
+        Block::Roster(program) => roster.push(program),
+    }
+
+    let end = source.lines().count() + 1;
+    let missing = |key: &str| ParseError::new(end, 1, format!("missing '{}'", key));
+    let level_index = try!(level_index.ok_or_else(|| missing("level")));
+    let phase = try!(phase.ok_or_else(|| missing("phase")));
+    let difficulty = try!(difficulty.ok_or_else(|| missing("difficulty")));
+    let turn = try!(turn.ok_or_else(|| missing("turn")));
+    if rows.is_empty() {
+        return Err(missing("row"));
+    }
+    if programs.iter().map(|&(_, ref program)| program).chain(roster.iter()).any(|program| program.name.is_empty()) {
+        return Err(missing("name"));
+    }
+
+    let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+    for row in rows.iter_mut() {
+        let padding = width - row.chars().count();
+        row.extend(::std::iter::repeat(' ').take(padding));
+    }
+    let mut level = Level::new(&rows);
+    level.title = title;
+    level.turn = turn;
+    for (id, program) in programs {
+        level.restore_program(id, program);
+    }
+
+    Ok(SaveFile {
+        level_index: level_index,
+        phase: phase,
+        difficulty: difficulty,
+        level: level,
+        roster: roster,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::{Direction, Point};
+    use engine::ai::Difficulty;
+    use engine::level::Level;
+    use engine::program::{Ability, Program, ProgramId, Shape, StatusEffect, Team};
+    use engine::tile::Tile;
+    use super::{parse, write, Phase, SaveFile};
+
+    fn save() -> SaveFile {
+        let rows = vec![" o.~1 ".to_owned(), " ..!1v".to_owned()];
+        let mut level = Level::new(&rows);
+        level.title = "Round trip".to_owned();
+        level.turn = Team::Player;
+
+        let mut hack = Program::new(Team::Player, Point::new(3, 2), "Hack");
+        hack.set_tail(vec![Point::new(2, 2)]);
+        hack.turn_state.moves_made = 1;
+        hack.abilities.push(("Slice".to_owned(), Ability::destroy(2, 1)));
+        hack.status_effects.push(StatusEffect::Haste { amount: 1, turns: 2 });
+        level.add_program(hack);
+
+        let mut patrol = Program::new(Team::Enemy, Point::new(3, 1), "Patrol");
+        patrol.turn_state.ability_used = true;
+        patrol.abilities.push(("Burst".to_owned(), Ability::destroy(1, 2).shape(Shape::Cross(1)).line_of_sight(true)));
+        patrol.status_effects.push(StatusEffect::Damage(1));
+        level.restore_program(ProgramId(4), patrol);
+
+        let mut spare = Program::new(Team::Player, Point::new(0, 0), "Bug");
+        spare.abilities.push(("Shield".to_owned(), Ability::status(StatusEffect::Shield { amount: 2, turns: 1 }, 1)));
+
+        SaveFile {
+            level_index: 2,
+            phase: Phase::PlayerTurn,
+            difficulty: Difficulty::Hard,
+            level: level,
+            roster: vec![spare],
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let save = save();
+        let text = write(&save);
+        let parsed = parse(&text).unwrap();
+        assert_eq!(write(&parsed), text);
+
+        assert_eq!(parsed.level_index, 2);
+        assert_eq!(parsed.phase, Phase::PlayerTurn);
+        assert_eq!(parsed.difficulty, Difficulty::Hard);
+        assert_eq!(parsed.level.layout, save.level.layout);
+        assert_eq!(parsed.level.programs.keys().cloned().collect::<Vec<_>>(), vec![ProgramId(0), ProgramId(4)]);
+
+        let hack = &parsed.level.programs[&ProgramId(0)];
+        assert_eq!(hack.position, Point::new(3, 2));
+        assert_eq!(hack.tail(), &[Point::new(2, 2)]);
+        assert_eq!(hack.turn_state.moves_made, 1);
+        assert_eq!(hack.abilities[0].1, Ability::destroy(2, 1));
+        assert_eq!(hack.status_effects, vec![StatusEffect::Haste { amount: 1, turns: 2 }]);
+        assert!(parsed.level.programs[&ProgramId(4)].turn_state.ability_used);
+        assert_eq!(parsed.roster[0].name, "Bug");
+    }
+
+    #[test]
+    fn keeps_void_at_the_ends_of_rows() {
+        let parsed = parse(&write(&save())).unwrap();
+        assert_eq!(parsed.level.width(), 6);
+        assert_eq!(parsed.level.tile(Point::new(1, 1)), Tile::Void);
+        assert_eq!(parsed.level.tile(Point::new(6, 2)), Tile::OneWay(Direction::Down));
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let text = write(&save()).replacen("version: 1", "version: 0", 1);
+        let err = parse(&text).err().unwrap();
+        assert_eq!((err.line, err.column), (1, 1));
+    }
+}
@@ -0,0 +1,261 @@
+use engine::{self, Point};
+use engine::action::Action;
+use engine::ai::{self, AIChoice, Settings};
+use engine::level::{self, Level};
+use engine::pathfinding::{self, DistanceField};
+use engine::program::{Ability, AbilityKind, Program, ProgramId, StatusEffect, Team};
+
+// How many of the best plans are checked against the opposing team's
+// reply, when the search goes that deep
+const REPLY_CANDIDATES: usize = 4;
+
+// One thing a program does during its turn
+#[derive(Clone,Copy,Debug)]
+pub enum PlanStep {
+    // Step onto the given tile
+    Move(Point),
+    UseAbility(Ability, Point),
+}
+
+// Everything on the level that a plan can change, counted from one
+// team's point of view
+#[derive(Clone,Copy,Debug,Default)]
+struct Tally {
+    damage: usize,
+    kills: usize,
+    // Threat of the programs killed
+    threat: usize,
+    debuffs: usize,
+    support: usize,
+}
+
+fn tally(level: &Level, caster: &Program) -> Tally {
+    let mut tally = Tally::default();
+    for program in level.programs.values() {
+        if program.team == caster.team {
+            for effect in program.status_effects.iter() {
+                match *effect {
+                    StatusEffect::Repair(amount) => tally.support += amount,
+                    effect if effect.beneficial() && effect.turns().is_some() => tally.support += 1,
+                    _ => {}
+                }
+            }
+        }
+        else {
+            let damage = ai::pending_damage(program);
+            tally.damage += ::std::cmp::min(damage, program.size());
+            if damage >= program.size() {
+                tally.kills += 1;
+                tally.threat += ai::threat(program);
+            }
+            tally.debuffs += program.status_effects.iter()
+                .filter(|effect| !effect.beneficial() && effect.turns().is_some())
+                .count();
+        }
+    }
+    tally
+}
+
+// Path distance from `point` to where the program can attack. The
+// program's own head blocks the field, so its starting tile is measured
+// through its neighbors.
+fn distance(level: &Level, field: &DistanceField, point: Point) -> Option<usize> {
+    field.distance(point).or_else(|| {
+        level::neighbors(point).into_iter()
+            .filter_map(|n| level.step(point, n))
+            .filter_map(|step| field.distance(step.destination).map(|d| d + step.cost))
+            .min()
+    })
+}
+
+fn evaluate(level: &Level, program: &Program, base: &Tally, field: &DistanceField, settings: &Settings) -> i32 {
+    let weights = &settings.weights;
+    let after = tally(level, program);
+
+    let mut score = 0;
+    score += weights.damage * (after.damage as i32 - base.damage as i32);
+    score += weights.kill * (after.kills as i32 - base.kills as i32);
+    score += weights.threat * (after.threat as i32 - base.threat as i32);
+    score += weights.status * (after.debuffs as i32 - base.debuffs as i32);
+    score += weights.support * (after.support as i32 - base.support as i32);
+
+    if let Some(distance) = distance(level, field, program.position) {
+        score -= weights.distance * distance as i32;
+    }
+    if ai::dead_end(level, program.position) {
+        score -= weights.dead_end;
+    }
+    score -= ai::hazard_penalty(level, program, program.position);
+    score
+}
+
+// Carries out everything pending on the level
+fn settle(level: &mut Level) {
+    while engine::resolve(level).modified {}
+}
+
+fn team_size(level: &Level, team: Team) -> usize {
+    level.programs.values().filter(|program| program.team == team).map(|program| program.size()).sum()
+}
+
+// Ends `team`'s turn on `level` and plays out the other team's reply,
+// each of its programs following the best plan a ply shallower than
+// `settings`. Returns how many tiles the reply costs `team`.
+fn reply(level: &Level, team: Team, settings: &Settings) -> usize {
+    let mut sim = level.clone();
+    settle(&mut sim);
+    let _ = Action::EndTurn(team).apply(&mut sim);
+    settle(&mut sim);
+    let before = team_size(&sim, team);
+
+    let other = match team {
+        Team::Player => Team::Enemy,
+        Team::Enemy => Team::Player,
+    };
+    sim.begin_turn(other);
+    let shallower = Settings { depth: settings.depth - 1, ..*settings };
+    for id in sim.team(other) {
+        if !sim.programs.contains_key(&id) {
+            continue;
+        }
+        for step in plan_turn(&sim, id, &shallower, &mut vec![]) {
+            if !execute(&mut sim, id, step) {
+                break;
+            }
+        }
+    }
+    settle(&mut sim);
+    before.saturating_sub(team_size(&sim, team))
+}
+
+// Best score first, then the shortest plan
+fn rank(plans: &mut Vec<(i32, Vec<PlanStep>, Point)>) {
+    plans.sort_by(|&(s1, ref p1, _), &(s2, ref p2, _)| (s2, p1.len()).cmp(&(s1, p2.len())));
+}
+
+impl PlanStep {
+    pub fn action(&self, program: ProgramId) -> Action {
+        match *self {
+            PlanStep::Move(to) => Action::Move(program, to),
+            PlanStep::UseAbility(ability, target) => Action::UseAbility(program, ability, target),
+        }
+    }
+
+    pub fn choice(&self) -> AIChoice {
+        match *self {
+            PlanStep::Move(to) => AIChoice::Move(to),
+            PlanStep::UseAbility(ability, target) => AIChoice::Ability { ability: ability, target: target },
+        }
+    }
+}
+
+// Carries out one step of a plan; false if it is no longer possible
+pub fn execute(level: &mut Level, program: ProgramId, step: PlanStep) -> bool {
+    step.action(program).apply(level).is_ok()
+}
+
+fn simulate(level: &Level, id: ProgramId, plan: &[PlanStep]) -> Option<Level> {
+    let mut sim = level.clone();
+    for &step in plan {
+        if !execute(&mut sim, id, step) {
+            return None;
+        }
+    }
+    Some(sim)
+}
+
+fn ability_steps(level: &Level, program: &Program) -> Vec<PlanStep> {
+    let mut steps = vec![];
+    if !program.can_use_ability() {
+        return steps;
+    }
+    for &(_, ability) in program.abilities.iter() {
+        match ability.kind {
+            AbilityKind::DeleteSector | AbilityKind::CreateSector => continue,
+            _ => {}
+        }
+        for target in ability.targets(level, program) {
+            steps.push(PlanStep::UseAbility(ability, target));
+        }
+    }
+    steps
+}
+
+// Tries every way the program `id` could spend the rest of its
+// turn: moving anywhere it can reach, and using an ability before or
+// after the move. From depth 2, the most promising plans are also
+// scored on what the other team's reply would cost. Returns the steps of
+// the best one; the tile each candidate ends on (or targets) is pushed
+// onto `considered` with its score.
+pub fn plan_turn(level: &Level, id: ProgramId, settings: &Settings, considered: &mut Vec<(Point, i32)>) -> Vec<PlanStep> {
+    let program = &level.programs[&id];
+    let targets = ai::attack_tiles(level, program);
+    let origin = program.position;
+    let paths = level.paths(program);
+    let field = pathfinding::distance_field(level, &targets);
+    let base = tally(level, program);
+    let opening = ability_steps(level, program);
+
+    let mut destinations = vec![origin];
+    destinations.extend(paths.reachable());
+
+    let mut candidates: Vec<Vec<PlanStep>> = vec![];
+    for destination in destinations {
+        if let Some(ref behavior) = program.behavior {
+            if !behavior.allows(program, destination) {
+                continue;
+            }
+        }
+        let moves: Vec<PlanStep> = match paths.path_to(destination) {
+            Some(path) => path.into_iter().map(PlanStep::Move).collect(),
+            None => continue,
+        };
+
+        for &first in opening.iter() {
+            let mut plan = vec![first];
+            plan.extend(moves.iter().cloned());
+            candidates.push(plan);
+        }
+        if let Some(sim) = simulate(level, id, &moves) {
+            for last in ability_steps(&sim, &sim.programs[&id]) {
+                let mut plan = moves.clone();
+                plan.push(last);
+                candidates.push(plan);
+            }
+        }
+        candidates.push(moves);
+    }
+
+    let mut scored = vec![];
+    for plan in candidates {
+        let sim = match simulate(level, id, &plan) {
+            Some(sim) => sim,
+            None => continue,
+        };
+        let score = evaluate(&sim, &sim.programs[&id], &base, &field, settings);
+        let tile = match plan.last() {
+            Some(&PlanStep::UseAbility(_, target)) => target,
+            _ => sim.programs[&id].position,
+        };
+        scored.push((score, plan, tile));
+    }
+    rank(&mut scored);
+
+    let checked = if settings.depth >= 2 { REPLY_CANDIDATES } else { 0 };
+    for entry in scored.iter_mut().take(checked) {
+        if let Some(sim) = simulate(level, id, &entry.1) {
+            entry.0 -= settings.weights.reply * reply(&sim, program.team, settings) as i32;
+        }
+    }
+    for &(score, _, tile) in scored.iter() {
+        considered.push((tile, score));
+    }
+    // Plans that weren't checked against the reply can't be compared
+    // with ones that were
+    if checked > 0 {
+        scored.truncate(checked);
+        rank(&mut scored);
+    }
+
+    scored.into_iter().next().map(|(_, plan, _)| plan).unwrap_or(vec![])
+}
@@ -0,0 +1,88 @@
+use engine::Direction;
+
+pub const HAZARD_DAMAGE: usize = 2;
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Tile {
+    Void,
+    Floor,
+    Uplink,
+    // Damages programs that end their turn on it
+    Hazard,
+    // Costs two moves to enter
+    Slow,
+    // Can only be entered moving in the given direction
+    OneWay(Direction),
+    // Moves programs to the other teleporter with the same channel
+    Teleporter(u8),
+}
+
+impl Tile {
+    pub fn from_char(c: char) -> Option<Tile> {
+        match c {
+            ' ' => Some(Tile::Void),
+            '.' => Some(Tile::Floor),
+            'o' => Some(Tile::Uplink),
+            '!' => Some(Tile::Hazard),
+            '~' => Some(Tile::Slow),
+            '^' => Some(Tile::OneWay(Direction::Up)),
+            'v' => Some(Tile::OneWay(Direction::Down)),
+            '<' => Some(Tile::OneWay(Direction::Left)),
+            '>' => Some(Tile::OneWay(Direction::Right)),
+            '0'...'9' => Some(Tile::Teleporter(c as u8 - b'0')),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(&self) -> char {
+        match *self {
+            Tile::Void => ' ',
+            Tile::Floor => '.',
+            Tile::Uplink => 'o',
+            Tile::Hazard => '!',
+            Tile::Slow => '~',
+            Tile::OneWay(Direction::Up) => '^',
+            Tile::OneWay(Direction::Down) => 'v',
+            Tile::OneWay(Direction::Left) => '<',
+            Tile::OneWay(Direction::Right) => '>',
+            Tile::Teleporter(channel) => (b'0' + channel) as char,
+        }
+    }
+
+    pub fn passable(&self) -> bool {
+        match *self {
+            Tile::Void | Tile::Uplink => false,
+            Tile::Floor | Tile::Hazard | Tile::Slow | Tile::OneWay(_) | Tile::Teleporter(_) => true,
+        }
+    }
+
+    pub fn blocks_sight(&self) -> bool {
+        *self == Tile::Void
+    }
+
+    pub fn is_uplink(&self) -> bool {
+        *self == Tile::Uplink
+    }
+
+    pub fn move_cost(&self) -> usize {
+        match *self {
+            Tile::Slow => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn glyph(&self) -> Option<char> {
+        match *self {
+            Tile::Void => None,
+            Tile::Floor => Some('·'),
+            Tile::Uplink => Some('Θ'),
+            Tile::Hazard => Some('!'),
+            Tile::Slow => Some('~'),
+            Tile::OneWay(Direction::Up) => Some('▲'),
+            Tile::OneWay(Direction::Down) => Some('▼'),
+            Tile::OneWay(Direction::Left) => Some('◀'),
+            Tile::OneWay(Direction::Right) => Some('▶'),
+            Tile::Teleporter(_) => Some('@'),
+        }
+    }
+}
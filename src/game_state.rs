@@ -2,18 +2,21 @@ use termion;
 use termion::event::{Key, Event, MouseEvent};
 
 use voodoo;
-use voodoo::window::{Point};
+use voodoo::window::{Point, Window};
 
-use ai;
-use data;
+use engine::{self, ai, data, Direction};
+use engine::action::Action;
+use engine::level::{Level, MoveSnapshot};
+use engine::program::{Ability, Program, Team};
+use engine::save_file::{Phase, SaveFile};
 use info_view::{self, InfoView};
 use level_transition;
 use map_view::MapView;
 use mission_select;
-use level::Level;
-use player::Player;
+use player::{Player, Stats};
 use player_turn;
-use program::{Ability, Program, StatusEffect, Team};
+use profile;
+use replay;
 
 
 #[derive(Clone,Copy,Debug)]
@@ -21,24 +24,27 @@ pub enum UiState {
     Unselected,
     Selected,
     SelectTarget(Ability),
+    // A shaped ability has been aimed and is waiting for confirmation
+    ConfirmTarget(Ability, engine::Point),
+    // A path to the given tile is shown and waiting for confirmation
+    PlannedMove(engine::Point),
+    // Following the planned path, one step per tick
+    Moving,
     Animating,
 }
 
-#[derive(Clone,Copy,Debug)]
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
 #[derive(Clone,Copy,Debug)]
 pub enum UiEvent {
     Quit,
     Tick,
-    ClickMap(Point),
+    ClickMap(engine::Point),
     ClickInfo(Point),
     Move(Direction),
+    Scroll(Direction),
+    ToggleDebug,
+    FastForward,
+    Save,
+    Undo,
     EndTurn,
 }
 
@@ -57,11 +63,19 @@ pub enum GameState {
 
 pub struct ModelView {
     pub level_index: usize,
+    pub ai: ai::AI,
     pub info: InfoView,
     pub map: MapView,
     pub player: Player,
     pub program_list: info_view::ChoiceList<Program>,
     pub level: Level,
+    // The player's moves this turn, most recent last
+    pub undo: Vec<MoveSnapshot>,
+    // The mission being recorded, if any
+    pub recording: Option<replay::Recording>,
+    // Whether a replay is driving the game, in which case nothing is
+    // saved
+    pub playback: bool,
 }
 
 impl GameState {
@@ -72,6 +86,15 @@ impl GameState {
             (_, Event::Key(Key::Char('s'))) => Some(UiEvent::Move(Direction::Down)),
             (_, Event::Key(Key::Char('a'))) => Some(UiEvent::Move(Direction::Left)),
             (_, Event::Key(Key::Char('d'))) => Some(UiEvent::Move(Direction::Right)),
+            (_, Event::Key(Key::Char('v'))) => Some(UiEvent::ToggleDebug),
+            (&GameState::AITurn(_), Event::Key(Key::Char('f'))) => Some(UiEvent::FastForward),
+            (&GameState::PlayerTurn(_), Event::Key(Key::Char('S'))) |
+            (&GameState::Setup(_), Event::Key(Key::Char('S'))) => Some(UiEvent::Save),
+            (&GameState::PlayerTurn(_), Event::Key(Key::Char('u'))) => Some(UiEvent::Undo),
+            (_, Event::Key(Key::Up)) => Some(UiEvent::Scroll(Direction::Up)),
+            (_, Event::Key(Key::Down)) => Some(UiEvent::Scroll(Direction::Down)),
+            (_, Event::Key(Key::Left)) => Some(UiEvent::Scroll(Direction::Left)),
+            (_, Event::Key(Key::Right)) => Some(UiEvent::Scroll(Direction::Right)),
             (&GameState::PlayerTurn(_), Event::Mouse(MouseEvent::Press(_, x, y))) |
             (&GameState::Setup(_), Event::Mouse(MouseEvent::Press(_, x, y))) => {
                 if let Some(p) = mv.map.from_global_frame(Point::new(x - 1, y - 1)) {
@@ -95,25 +118,68 @@ impl GameState {
 
     pub fn next(self, event: termion::event::Event, mv: &mut ModelView) -> GameState {
         match (self, event) {
-            (GameState::MissionSelect(ms), Event::Key(_)) => Self::next_mission_turn(ms, mission_select::UiEvent::KeyPressed, mv),
+            (GameState::MissionSelect(ms), Event::Key(key)) => Self::next_mission_turn(ms, mission_select::UiEvent::KeyPressed(key), mv),
+            (GameState::MissionSelect(ms), Event::Mouse(MouseEvent::Press(_, x, y))) => {
+                Self::next_mission_turn(ms, mission_select::UiEvent::Click(Point::new(x - 1, y - 1)), mv)
+            }
             (GameState::LevelTransition(lt), Event::Key(_)) => Self::next_transition_turn(lt, level_transition::UiEvent::KeyPressed, mv),
             (state, _) => {
                 if let Some(event) = state.translate_event(event, mv) {
+                    mv.map.clear_notice();
+                    if let UiEvent::Save = event {
+                        let phase = match state {
+                            GameState::Setup(_) => Phase::Setup,
+                            _ => Phase::PlayerTurn,
+                        };
+                        match save(phase, mv) {
+                            Ok(()) if mv.playback => mv.map.notify("Not saved during a replay"),
+                            Ok(()) => mv.map.notify("Mission saved"),
+                            Err(err) => mv.map.notify(format!("Could not save: {}", err)),
+                        }
+                        return state;
+                    }
+                    if let UiEvent::Scroll(direction) = event {
+                        mv.map.scroll(direction, &mv.level);
+                        return state;
+                    }
+                    if let UiEvent::ToggleDebug = event {
+                        mv.ai.debug = !mv.ai.debug;
+                        if !mv.ai.debug {
+                            mv.map.clear_scores();
+                            if let GameState::AITurn(_) = state {
+                                mv.info.clear();
+                            }
+                        }
+                        return state;
+                    }
+
                     match state {
                         GameState::Setup(ui) => Self::next_setup_turn(ui, event, mv),
                         GameState::PlayerTurn(ui) => match event {
                             UiEvent::EndTurn => {
                                 match mv.level.check_victory() {
-                                    Some(team) => GameState::LevelTransition(level_transition::State::new(mv.level_index, team)),
+                                    Some(team) => end_mission(team, mv),
                                     None => GameState::AITurnTransition
                                 }
                             },
                             _ => Self::next_player_turn(ui, event, mv)
                         },
+                        GameState::AITurn(_) => match event {
+                            UiEvent::FastForward => {
+                                // Play out the rest of the turn at once
+                                mv.ai.fast_forward();
+                                let mut state = state;
+                                while let GameState::AITurn(_) = state {
+                                    state = state.tick(mv);
+                                }
+                                state
+                            }
+                            _ => state,
+                        },
                         GameState::MissionSelect(_) | GameState::LevelTransition(_) => state,
                         GameState::SetupTransition |
                         GameState::AITurnTransition | GameState::PlayerTurnTransition |
-                        GameState::AITurn(_) | GameState::Quit => state,
+                        GameState::Quit => state,
                     }
                 }
                 else {
@@ -130,20 +196,38 @@ impl GameState {
             GameState::MissionSelect(ms) => Self::next_mission_turn(ms, mission_select::UiEvent::Tick, mv),
             GameState::LevelTransition(lt) => Self::next_transition_turn(lt, level_transition::UiEvent::Tick, mv),
             GameState::AITurnTransition => {
+                let damaged = Action::EndTurn(Team::Player).apply(&mut mv.level).unwrap_or(false);
                 begin_turn(Team::Enemy, mv);
-                GameState::AITurn(UiState::Unselected)
+                mv.ai.begin_turn();
+                if damaged {
+                    GameState::AITurn(UiState::Animating)
+                }
+                else {
+                    GameState::AITurn(UiState::Unselected)
+                }
             }
             GameState::PlayerTurnTransition => {
                 match mv.level.check_victory() {
-                    Some(team) => GameState::LevelTransition(level_transition::State::new(mv.level_index, team)),
+                    Some(team) => end_mission(team, mv),
                     None => {
+                        // Fails straight after setup, before the enemy
+                        // has had a turn to end
+                        let damaged = Action::EndTurn(Team::Enemy).apply(&mut mv.level).unwrap_or(false);
                         begin_turn(Team::Player, mv);
-                        GameState::PlayerTurn(UiState::Unselected)
+                        if let Err(err) = save(Phase::PlayerTurn, mv) {
+                            mv.map.notify(format!("Could not save: {}", err));
+                        }
+                        if damaged {
+                            GameState::PlayerTurn(UiState::Animating)
+                        }
+                        else {
+                            GameState::PlayerTurn(UiState::Unselected)
+                        }
                     }
                 }
             }
             GameState::AITurn(UiState::Animating) => {
-                let modified = update_programs(&mut mv.level, &mut mv.map);
+                let modified = update_programs(&mut mv.level, &mut mv.map, &mut mv.player.stats);
 
                 if !modified {
                     GameState::AITurn(UiState::Unselected)
@@ -153,8 +237,20 @@ impl GameState {
                 }
             }
             GameState::AITurn(_) => {
-                let ai_state = ai::ai_tick(&mut mv.level, &mut mv.map);
-                mv.map.set_help(format!("AI STATUS: {:?}", ai_state));
+                mv.map.age_trail();
+                let ai_state = mv.ai.tick(&mut mv.level);
+                match mv.ai.focus {
+                    Some(program) => mv.map.focus(program, &mv.level),
+                    None => mv.map.clear_focus(),
+                }
+                if let Some(p) = mv.ai.moved_from {
+                    mv.map.add_trail(p);
+                }
+                mv.map.set_help(format!("AI STATUS: {:?} (f to skip)", ai_state));
+                if mv.ai.debug {
+                    mv.map.show_scores(&mv.ai.debug_info.considered);
+                    mv.info.display_ai_debug(&mv.ai.debug_info);
+                }
                 match ai_state {
                     ai::AIState::Done => GameState::PlayerTurnTransition,
                     ai::AIState::Plotting => GameState::AITurn(UiState::Unselected),
@@ -167,8 +263,14 @@ impl GameState {
                 mv.info.primary_action = ">Launch Intrusion<".to_owned();
                 mv.info.display_end_turn();
                 mv.map.display(&mv.level);
+                // Leave out anything already loaded, in case the mission
+                // was resumed partway through setup
+                let deployed: Vec<String> = mv.level.programs.values()
+                    .filter(|program| program.team == Team::Player)
+                    .map(|program| program.name.clone())
+                    .collect();
                 mv.program_list.choices().clear();
-                mv.program_list.choices().extend(mv.player.programs.iter().map(|x| {
+                mv.program_list.choices().extend(mv.player.programs.iter().filter(|x| !deployed.contains(&x.name)).map(|x| {
                     (x.name.to_owned(), x.clone())
                 }));
                 begin_turn(Team::Player, mv);
@@ -198,24 +300,29 @@ impl GameState {
 
     pub fn next_player_turn(ui_state: UiState, event: UiEvent, mv: &mut ModelView) -> GameState {
         match event {
-            UiEvent::ClickMap(_) | UiEvent::ClickInfo(_) | UiEvent::Tick | UiEvent::Move(_) => {
+            UiEvent::ClickMap(_) | UiEvent::ClickInfo(_) | UiEvent::Tick | UiEvent::Move(_) | UiEvent::Undo => {
                 GameState::PlayerTurn(player_turn::next(ui_state, event, mv))
             }
-            UiEvent::EndTurn | UiEvent::Quit => unreachable!(),
+            UiEvent::EndTurn | UiEvent::Quit | UiEvent::Scroll(_) | UiEvent::ToggleDebug | UiEvent::FastForward | UiEvent::Save => unreachable!(),
         }
     }
 
     pub fn next_mission_turn(mut mission_state: mission_select::State, event: mission_select::UiEvent, mv: &mut ModelView) -> GameState {
         match mission_select::next(&mut mission_state, event, mv) {
-            mission_select::Transition::Ui(_) => GameState::MissionSelect(mission_state),
+            mission_select::Transition::Ui => GameState::MissionSelect(mission_state),
+            mission_select::Transition::Resume(save) => {
+                let phase = save.phase;
+                let state = resume(save, mv);
+                start_recording(phase, mv);
+                state
+            }
             mission_select::Transition::Level(index) => {
-                if let Some(level) = data::load_level(index) {
-                    mv.level_index = index;
-                    mv.level = level;
-                    GameState::SetupTransition
-                }
-                else {
-                    GameState::Quit
+                match start_level(index, mv) {
+                    Ok(state) => state,
+                    Err(err) => {
+                        mission_state.error = Some(format!("Could not load level: {}", err));
+                        GameState::MissionSelect(mission_state)
+                    }
                 }
             }
         }
@@ -224,13 +331,13 @@ impl GameState {
     pub fn next_transition_turn(mut state: level_transition::State, event: level_transition::UiEvent, mv: &mut ModelView) -> GameState {
         match level_transition::next(&mut state, event, mv) {
             Some(index) => {
-                if let Some(level) = data::load_level(index) {
-                    mv.level_index = index;
-                    mv.level = level;
-                    GameState::SetupTransition
-                }
-                else {
-                    GameState::Quit
+                match start_level(index, mv) {
+                    Ok(state) => state,
+                    Err(err) => {
+                        let mut mission_state = mission_select::State::new(Window::new(Point::new(0, 0), 80, 24));
+                        mission_state.error = Some(format!("Could not load level: {}", err));
+                        GameState::MissionSelect(mission_state)
+                    }
                 }
             },
             None => {
@@ -250,7 +357,7 @@ impl GameState {
                 mv.info.display_end_turn();
                 GameState::PlayerTurnTransition
             }
-            UiEvent::Quit => unreachable!(),
+            UiEvent::Quit | UiEvent::Scroll(_) | UiEvent::ToggleDebug | UiEvent::FastForward | UiEvent::Save | UiEvent::Undo => unreachable!(),
         }
     }
 }
@@ -258,47 +365,118 @@ impl GameState {
 pub fn begin_turn(team: Team, mv: &mut ModelView) {
     mv.info.set_team(team);
     mv.info.clear();
+    mv.map.clear_scores();
     mv.map.clear_range();
     mv.map.clear_highlight();
     mv.map.update_highlight(&mut mv.level);
-    mv.level.begin_turn();
+    mv.level.begin_turn(team);
+    mv.undo.clear();
 }
 
-pub fn update_programs(level: &mut Level, map: &mut MapView) -> bool {
-    let mut modified = false;
-    let mut killed = vec![];
-    for program in level.programs.iter_mut() {
-        let mut p = program.borrow_mut();
-        let position = p.position;
-        let mut damaged = false;
-        for effect in p.status_effects.iter_mut() {
-            match *effect {
-                StatusEffect::Damage(damage) => {
-                    modified = true;
-                    damaged = true;
-                    *effect = StatusEffect::Damage(damage - 1);
-                }
-            }
+fn snapshot(phase: Phase, mv: &ModelView) -> SaveFile {
+    SaveFile {
+        level_index: mv.level_index,
+        phase: phase,
+        difficulty: mv.ai.difficulty,
+        level: mv.level.clone(),
+        roster: mv.player.programs.clone(),
+    }
+}
+
+// Writes the mission out so it can be resumed from the title screen
+fn save(phase: Phase, mv: &ModelView) -> Result<(), data::LoadError> {
+    if mv.playback {
+        return Ok(());
+    }
+    data::save_mission(&snapshot(phase, mv))
+}
+
+// Starts recording a replay of the mission from where it stands
+fn start_recording(phase: Phase, mv: &mut ModelView) {
+    finish_recording(mv);
+    if !mv.playback {
+        mv.recording = Some(replay::Recording::new(&snapshot(phase, mv)));
+    }
+}
+
+// Writes out the replay of the mission, if one is being recorded
+pub fn finish_recording(mv: &mut ModelView) {
+    if let Some(recording) = mv.recording.take() {
+        // Losing a replay isn't worth interrupting the game over
+        let _ = replay::save(&recording);
+    }
+}
+
+pub fn resume(save: SaveFile, mv: &mut ModelView) -> GameState {
+    mv.level_index = save.level_index;
+    mv.level = save.level;
+    mv.ai.difficulty = save.difficulty;
+    mv.ai.begin_turn();
+    mv.player.programs = save.roster;
+    mv.undo.clear();
+
+    match save.phase {
+        Phase::Setup => GameState::SetupTransition,
+        Phase::PlayerTurn => {
+            mv.map.reset();
+            mv.info.set_team(Team::Player);
+            mv.info.primary_action = ">    End Turn    <".to_owned();
+            mv.info.clear();
+            // Plays out any damage that was still pending when saved
+            GameState::PlayerTurn(UiState::Animating)
         }
-        p.status_effects.retain(|effect| {
-            match *effect {
-                StatusEffect::Damage(0) => false,
-                StatusEffect::Damage(_) => true,
-            }
-        });
+    }
+}
 
-        if damaged {
-            let lived = p.damage();
-            if !lived {
-                killed.push(position);
-                map.clear_highlight();
-            }
+// Loads the level and starts setting it up. Running out of levels ends
+// the game; a level that can't be read is left to the caller.
+fn start_level(index: usize, mv: &mut ModelView) -> Result<GameState, data::LoadError> {
+    match data::load_level(index) {
+        Ok(level) => {
+            mv.level_index = index;
+            mv.level = level;
+            start_recording(Phase::Setup, mv);
+            Ok(GameState::SetupTransition)
         }
+        Err(data::LoadError::NotFound(_)) => Ok(GameState::Quit),
+        Err(err) => Err(err),
     }
+}
 
-    for position in killed {
-        level.remove_program_at(position);
+// Records the outcome in the campaign profile
+fn end_mission(winner: Team, mv: &mut ModelView) -> GameState {
+    finish_recording(mv);
+    if mv.playback {
+        return GameState::LevelTransition(level_transition::State::new(mv.level_index, winner));
+    }
+
+    data::clear_mission();
+    match winner {
+        Team::Player => {
+            mv.player.complete(mv.level_index);
+            mv.player.stats.missions_won += 1;
+        }
+        Team::Enemy => mv.player.stats.missions_lost += 1,
     }
 
-    modified
+    let mut state = level_transition::State::new(mv.level_index, winner);
+    if let Err(err) = profile::save(&mv.player) {
+        state.error = Some(format!("Could not save progress: {}", err));
+    }
+    GameState::LevelTransition(state)
+}
+
+pub fn update_programs(level: &mut Level, map: &mut MapView, stats: &mut Stats) -> bool {
+    let resolution = engine::resolve(level);
+    if !resolution.killed.is_empty() {
+        map.clear_highlight();
+        map.clear_focus();
+    }
+    for &(_, team) in resolution.killed.iter() {
+        match team {
+            Team::Player => stats.programs_lost += 1,
+            Team::Enemy => stats.enemies_destroyed += 1,
+        }
+    }
+    resolution.modified
 }
@@ -1,7 +1,8 @@
 use voodoo::color::ColorValue;
 use voodoo::window::{FormattedString, Point, Window};
 
-use program::{Ability, Program, Team};
+use engine::ai::DebugInfo;
+use engine::program::{Ability, Program, Team};
 
 pub struct ChoiceList<T> {
     y: u16,
@@ -77,6 +78,16 @@ impl<T> ChoiceList<T> {
     }
 }
 
+// Where status effects are listed, below the ability list
+const EFFECTS_Y: u16 = 17;
+const MAX_EFFECTS: usize = 5;
+// Where the AI debug panel lists its best scores, then its choices. The
+// scores double as the key to the rank digits on the map.
+const SCORES_Y: u16 = 3;
+const MAX_SCORES: usize = 10;
+const CHOICES_Y: u16 = 15;
+const MAX_CHOICES: usize = 7;
+
 pub struct InfoView {
     pub window: Window,
     ability_list: ChoiceList<Ability>,
@@ -136,7 +147,10 @@ impl InfoView {
         self.update_program(program);
 
         self.ability_list.clear();
-        if program.turn_state.ability_used {
+        if program.stunned() {
+            self.window.print_at(Point::new(2, 4), "Stunned");
+        }
+        else if program.turn_state.ability_used {
             self.window.print_at(Point::new(2, 4), "Ability used");
         }
         else {
@@ -145,12 +159,44 @@ impl InfoView {
 
             self.display_abilities();
         }
+
+        let effects: Vec<String> = program.status_effects.iter()
+            .filter(|effect| effect.turns().is_some())
+            .map(|effect| effect.describe())
+            .collect();
+        if !effects.is_empty() {
+            self.window.print_at(Point::new(2, EFFECTS_Y), "Effects:");
+            for (offset, effect) in effects.iter().take(MAX_EFFECTS).enumerate() {
+                self.window.print_at(Point::new(2, EFFECTS_Y + 1 + offset as u16), effect);
+            }
+        }
+    }
+
+    // Lists the best scores the acting program found, by the rank shown
+    // on the map, then the latest steps the AI chose
+    pub fn display_ai_debug(&mut self, debug: &DebugInfo) {
+        let mut ranked = debug.considered.clone();
+        ranked.sort_by(|&(_, s1), &(_, s2)| s2.cmp(&s1));
+        self.window.print_at(Point::new(2, SCORES_Y), "Map key:         ");
+        for offset in 0..MAX_SCORES {
+            let line = match ranked.get(offset) {
+                Some(&(p, score)) => format!("{} {},{} = {}", offset, p.x, p.y, score),
+                None => String::new(),
+            };
+            self.window.print_at(Point::new(2, SCORES_Y + 1 + offset as u16), &format!("{:<17.17}", line));
+        }
+
+        self.window.print_at(Point::new(2, CHOICES_Y), "Choices:         ");
+        let skip = debug.log.len().saturating_sub(MAX_CHOICES);
+        for offset in 0..MAX_CHOICES {
+            let line = debug.log.get(skip + offset).map_or("", |line| &line[..]);
+            self.window.print_at(Point::new(2, CHOICES_Y + 1 + offset as u16), &format!("{:<17.17}", line));
+        }
     }
 
     pub fn update_program(&mut self, program: &Program) {
-        self.window.print_at(
-            Point::new(2, 3),
-            &format!("Moves: {}/{}", program.max_moves - program.turn_state.moves_made, program.max_moves));
+        let moves = format!("Moves: {}/{}", program.moves_left(), program.moves_allowed());
+        self.window.print_at(Point::new(2, 3), &format!("{:<17}", moves));
     }
 
     pub fn translate_click(&mut self, click: Point) -> Option<Ability> {
@@ -1,9 +1,7 @@
 use voodoo::window::{Point, Window};
 
-use data;
-use game_state::{UiState, ModelView};
-use level::Level;
-use program::Team;
+use engine::program::Team;
+use game_state::ModelView;
 
 const DEFEAT: [&'static str; 6] = [
     "██████╗ ███████╗███████╗███████╗ █████╗ ████████╗",
@@ -32,6 +30,8 @@ pub struct State {
     level_index: usize,
     window: Window,
     winning_team: Team,
+    // Why the campaign profile couldn't be saved
+    pub error: Option<String>,
 }
 
 impl State {
@@ -40,6 +40,7 @@ impl State {
             level_index: level_index,
             window: Window::new(Point::new(0, 0), 80, 24),
             winning_team: winning_team,
+            error: None,
         }
     }
 }
@@ -50,12 +51,12 @@ impl ::std::fmt::Debug for State {
     }
 }
 
-pub fn next(state: &mut State, event: UiEvent, _mv: &mut ModelView) -> Option<usize> {
+pub fn next(state: &mut State, event: UiEvent, mv: &mut ModelView) -> Option<usize> {
     use self::UiEvent::*;
     match event {
         KeyPressed => {
             match state.winning_team {
-                Team::Player => Some(state.level_index + 1),
+                Team::Player => Some(mv.player.next_level()),
                 Team::Enemy => Some(state.level_index),
             }
         },
@@ -74,5 +75,8 @@ pub fn display(state: &mut State, compositor: &mut ::voodoo::compositor::Composi
     }
 
     state.window.print_at(Point::new(30, 14), message);
+    if let Some(ref error) = state.error {
+        state.window.print_at(Point::new(2, 22), &format!("{:^76.76}", error));
+    }
     state.window.refresh(compositor);
 }
@@ -5,17 +5,16 @@ extern crate thread_scoped;
 extern crate time;
 extern crate voodoo;
 
-mod ai;
-mod data;
+mod engine;
 mod game_state;
 mod info_view;
 mod level_transition;
 mod map_view;
 mod mission_select;
-mod level;
 mod player;
 mod player_turn;
-mod program;
+mod profile;
+mod replay;
 
 use std::io::{Write};
 use std::sync::mpsc::channel;
@@ -26,6 +25,8 @@ use termion::input::{TermRead};
 use voodoo::color::ColorValue;
 use voodoo::window::{Point};
 
+use engine::{ai, program};
+use engine::level::Level;
 use info_view::InfoView;
 use map_view::MapView;
 use player::Player;
@@ -33,6 +34,52 @@ use player::Player;
 const MS: u64 = 1_000_000;
 const TICK_TIME: u64 = 100;
 
+// The player's starting roster, for when there is no profile yet
+fn new_campaign() -> Player {
+    let mut player = Player::new("David");
+
+    let prog_builder = program::ProgramBuilder::new("Hack 1")
+        .ability("Bitblast", program::Ability::destroy(3, 1))
+        .max_tail(5)
+        .max_moves(4);
+
+    player.programs.push(prog_builder.instance(program::Team::Player));
+    player.programs.push(prog_builder.name("Hack 2").instance(program::Team::Player));
+    player.programs.push(program::ProgramBuilder::new("Sprinter")
+                         .max_tail(2)
+                         .max_moves(10)
+                         .ability("Overflow", program::Ability::destroy(1, 3).line_of_sight(true))
+                         .ability("Spray", program::Ability::destroy(1, 2).shape(program::Shape::Cone))
+                         .instance(program::Team::Player));
+    player.programs.push(program::ProgramBuilder::new("Cannon")
+                         .max_tail(1)
+                         .max_moves(4)
+                         .ability("Shred", program::Ability::destroy(6, 5).line_of_sight(true))
+                         .ability("Scatter", program::Ability::destroy(2, 4).shape(program::Shape::Splash(1)).line_of_sight(true))
+                         .instance(program::Team::Player));
+    player.programs.push(program::ProgramBuilder::new("Architect")
+                         .max_tail(3)
+                         .max_moves(3)
+                         .ability("Carve", program::Ability::delete_sector(2))
+                         .ability("Bridge", program::Ability::create_sector(2))
+                         .instance(program::Team::Player));
+    player.programs.push(program::ProgramBuilder::new("Medic")
+                         .max_tail(3)
+                         .max_moves(3)
+                         .ability("Patch", program::Ability::repair(2, 2))
+                         .instance(program::Team::Player));
+    player.programs.push(program::ProgramBuilder::new("Warden")
+                         .max_tail(3)
+                         .max_moves(3)
+                         .ability("Bulwark", program::Ability::status(
+                             program::StatusEffect::Shield { amount: 3, turns: 2 }, 2))
+                         .ability("Overclock", program::Ability::status(
+                             program::StatusEffect::Haste { amount: 2, turns: 1 }, 2))
+                         .instance(program::Team::Player));
+
+    player
+}
+
 fn main() {
     use std::sync::mpsc::TryRecvError::*;
     use std::thread;
@@ -43,7 +90,6 @@ fn main() {
     use voodoo::terminal::{Mode, Terminal};
     use voodoo::window::{Window};
 
-    let level = data::load_level(0).expect("No levels defined!");
     let mut terminal = Terminal::new();
     terminal.cursor(Mode::Disabled);
     terminal.clear_color(ColorValue::Black);
@@ -59,37 +105,49 @@ fn main() {
 
     let info_view = InfoView::new(info);
     let map_view = MapView::new(map);
-    let mut player = Player::new("David");
-
-    let prog_builder = program::ProgramBuilder::new("Hack 1")
-        .ability("Bitblast", program::Ability::Destroy { damage: 3, range: 1 })
-        .max_tail(5)
-        .max_moves(4);
-
-    player.programs.push(prog_builder.instance(program::Team::Player));
-    player.programs.push(prog_builder.name("Hack 2").instance(program::Team::Player));
-    player.programs.push(program::ProgramBuilder::new("Sprinter")
-                         .max_tail(2)
-                         .max_moves(10)
-                         .ability("Overflow", program::Ability::Destroy { damage: 1, range: 3 })
-                         .instance(program::Team::Player));
-    player.programs.push(program::ProgramBuilder::new("Cannon")
-                         .max_tail(1)
-                         .max_moves(4)
-                         .ability("Shred", program::Ability::Destroy { damage: 6, range: 5 })
-                         .instance(program::Team::Player));
+    // A profile that can't be read shouldn't keep the game from starting
+    let (player, profile_error) = match profile::load() {
+        Ok(Some(player)) => (player, None),
+        Ok(None) => (new_campaign(), None),
+        Err(err) => (new_campaign(), Some(format!("Could not load campaign profile, starting a new one: {}", err))),
+    };
 
     let mut mv = ModelView {
         level_index: 0,
+        ai: ai::AI::new(ai::Difficulty::Normal),
         info: info_view,
         map: map_view,
         player: player,
         program_list: info_view::ChoiceList::new(4),
-        level: level,
+        // Replaced once a mission is launched
+        level: Level::new(&[String::new()]),
+        undo: vec![],
+        recording: None,
+        playback: false,
     };
 
-    let title_state = mission_select::State::new(title);
+    let mut title_state = mission_select::State::new(title);
+    title_state.error = profile_error;
+
+    // `--replay <path>` plays a recorded mission back instead
+    let mut args = std::env::args().skip(1);
+    let mut replay = None;
+    if let Some("--replay") = args.next().as_ref().map(|arg| arg.as_str()) {
+        let path = args.next().unwrap_or("last.replay".to_owned());
+        match replay::load(&path) {
+            Ok(loaded) => replay = Some(loaded),
+            Err(err) => title_state.error = Some(format!("Could not load replay: {}", err)),
+        }
+    }
+
     let mut state = GameState::MissionSelect(title_state);
+    let mut playback = None;
+    if let Some(replay) = replay {
+        mv.playback = true;
+        state = game_state::resume(replay.mission, &mut mv);
+        playback = Some(replay::Playback::new(replay.inputs));
+    }
+    let mut playback_bar = Window::new(Point::new(22, 0), 40, 1);
 
     let (tx, rx) = channel();
     let guard = unsafe {
@@ -113,6 +171,16 @@ fn main() {
             let msg = rx.try_recv();
             match msg {
                 Ok(evt) => {
+                    // While playing back, the keyboard only controls the replay
+                    if let Some(ref mut playback) = playback {
+                        if let Event::Key(key) = evt {
+                            playback.control(key);
+                        }
+                        continue;
+                    }
+                    if let Some(ref mut recording) = mv.recording {
+                        recording.record(replay::Input::Event(evt.clone()));
+                    }
                     state = state.next(evt, &mut mv);
                     if let GameState::Quit = state {
                         state = GameState::MissionSelect(mission_select::State::new(Window::new(Point::new(0, 0), 80, 24)));
@@ -127,18 +195,47 @@ fn main() {
         dt += now - t;
 
         while dt >= TICK_TIME * MS {
-            state = state.tick(&mut mv);
+            if let Some(ref mut playback) = playback {
+                for input in playback.next_inputs() {
+                    state = match input {
+                        replay::Input::Event(evt) => state.next(evt, &mut mv),
+                        replay::Input::Tick => state.tick(&mut mv),
+                    };
+                    if let GameState::Quit = state {
+                        break;
+                    }
+                }
+            }
+            else {
+                if let Some(ref mut recording) = mv.recording {
+                    recording.record(replay::Input::Tick);
+                }
+                state = state.tick(&mut mv);
+            }
             if let GameState::Quit = state {
-                break 'main;
+                if playback.is_none() {
+                    break 'main;
+                }
+                // A replay that quits goes back to the mission select,
+                // the same as live input, and hands the keyboard back
+                playback = None;
+                mv.playback = false;
+                state = GameState::MissionSelect(mission_select::State::new(Window::new(Point::new(0, 0), 80, 24)));
             }
             dt -= TICK_TIME * MS;
         }
 
         state.display(&mut compositor, &mut mv);
+        if let Some(ref playback) = playback {
+            playback_bar.clear();
+            playback_bar.print_at(Point::new(0, 0), &playback.status());
+            playback_bar.refresh(&mut compositor);
+        }
         compositor.refresh(stdout);
         t = now;
 
         thread::sleep(Duration::from_millis((TICK_TIME - dt / MS) / 2));
     }
+    game_state::finish_recording(&mut mv);
     guard.join();
 }
@@ -1,18 +1,65 @@
 use std::collections::HashMap;
 
 use voodoo::color::ColorValue;
-use voodoo::window::{Point, TermCell, Window};
+use voodoo::window::{self, TermCell, Window};
 
-use level::{CellContents, Level};
-use program::{ProgramRef, Team};
+use engine::{Direction, Point};
+use engine::level::{CellContents, Level};
+use engine::program::{Ability, Program, ProgramId, Team};
+use engine::tile::Tile;
+
+// Size of the visible part of the level, in tiles
+const VIEW_WIDTH: u16 = 57;
+const VIEW_HEIGHT: u16 = 20;
+// How close the highlighted program may get to the edge before scrolling
+const FOLLOW_MARGIN: u16 = 4;
+// How many ticks a step stays in the acting program's trail
+const TRAIL_LENGTH: usize = 3;
+
+fn tile_color(tile: Tile) -> Option<ColorValue> {
+    match tile {
+        Tile::Uplink => Some(ColorValue::Yellow),
+        Tile::Hazard => Some(ColorValue::Red),
+        Tile::Teleporter(_) => Some(ColorValue::Cyan),
+        _ => None,
+    }
+}
+
+fn tile_cell(tile: Tile) -> Option<TermCell> {
+    tile.glyph().map(|c| {
+        let mut tc: TermCell = c.into();
+        tc.bg = tile_color(tile);
+        tc
+    })
+}
+
+fn program_cells(program: &Program, color: ColorValue) -> Vec<(Point, TermCell)> {
+    program.glyphs().into_iter().map(|(p, c)| {
+        let mut tc: TermCell = c.into();
+        tc.bg = Some(color);
+        (p, tc)
+    }).collect()
+}
 
 pub struct MapView {
     window: Window,
-    highlight: Option<ProgramRef>,
-    highlight_range: Option<usize>,
+    scroll: Point,
+    highlight: Option<ProgramId>,
+    highlight_range: Option<Ability>,
+    // The enemy program currently acting, and the tiles it just left
+    // along with how many ticks ago
+    focus: Option<ProgramId>,
+    trail: Vec<(Point, usize)>,
     overlay: Vec<(Point, TermCell)>,
+    reachable: Vec<Point>,
+    path: Vec<Point>,
+    area_preview: Vec<(Point, TermCell)>,
     named_overlay: HashMap<String, (Point, TermCell)>,
+    // Tiles the AI considered, marked by how their scores rank
+    scores: Vec<(Point, TermCell)>,
     help: Option<String>,
+    // Shown instead of the help until the player's next input
+    notice: Option<String>,
 }
 
 impl MapView {
@@ -20,69 +67,190 @@ impl MapView {
         window.border();
         MapView {
             window: window,
+            scroll: Point::new(0, 0),
             highlight: None,
             highlight_range: None,
+            focus: None,
+            trail: Vec::new(),
             overlay: Vec::new(),
+            reachable: Vec::new(),
+            path: Vec::new(),
+            area_preview: Vec::new(),
             named_overlay: HashMap::new(),
+            scores: Vec::new(),
             help: None,
+            notice: None,
         }
     }
 
     pub fn reset(&mut self) {
         self.overlay.clear();
         self.named_overlay.clear();
+        self.scores.clear();
         self.clear_help();
+        self.clear_notice();
         self.clear_highlight();
+        self.clear_focus();
+        self.scroll = Point::new(0, 0);
     }
 
     pub fn get_overlay(&mut self) -> &mut HashMap<String, (Point, TermCell)> {
         &mut self.named_overlay
     }
 
-    pub fn from_global_frame(&self, p: Point) -> Option<Point> {
-        self.window.position.from_global_frame(p)
+    // Translates a click into level coordinates, taking scrolling into account
+    pub fn from_global_frame(&self, p: window::Point) -> Option<Point> {
+        self.window.position.from_global_frame(p).and_then(|p| {
+            if p.x < 1 || p.y < 1 || p.x > VIEW_WIDTH || p.y > VIEW_HEIGHT {
+                None
+            }
+            else {
+                Some(Point::new(p.x + self.scroll.x, p.y + self.scroll.y))
+            }
+        })
+    }
+
+    fn to_window_frame(&self, p: Point) -> Option<window::Point> {
+        if p.x <= self.scroll.x || p.y <= self.scroll.y {
+            return None;
+        }
+
+        let x = p.x - self.scroll.x;
+        let y = p.y - self.scroll.y;
+        if x > VIEW_WIDTH || y > VIEW_HEIGHT {
+            None
+        }
+        else {
+            Some(window::Point::new(x + 1, y + 1))
+        }
+    }
+
+    fn put_at(&mut self, p: Point, tc: TermCell) {
+        if let Some(p) = self.to_window_frame(p) {
+            self.window.put_at(p, tc);
+        }
+    }
+
+    pub fn scroll(&mut self, direction: Direction, level: &Level) {
+        use engine::Direction::*;
+
+        let Point { x, y } = self.scroll;
+        self.scroll = match direction {
+            Up => Point::new(x, y.saturating_sub(1)),
+            Down => Point::new(x, y + 1),
+            Left => Point::new(x.saturating_sub(1), y),
+            Right => Point::new(x + 1, y),
+        };
+        self.clamp_scroll(level);
+    }
+
+    // Scroll just enough to keep the given point away from the edges
+    pub fn follow(&mut self, p: Point, level: &Level) {
+        let margin_x = ::std::cmp::min(FOLLOW_MARGIN, VIEW_WIDTH / 2);
+        let margin_y = ::std::cmp::min(FOLLOW_MARGIN, VIEW_HEIGHT / 2);
+
+        if p.x <= self.scroll.x + margin_x {
+            self.scroll.x = p.x.saturating_sub(margin_x + 1);
+        }
+        else if p.x > self.scroll.x + VIEW_WIDTH - margin_x {
+            self.scroll.x = p.x + margin_x - VIEW_WIDTH;
+        }
+
+        if p.y <= self.scroll.y + margin_y {
+            self.scroll.y = p.y.saturating_sub(margin_y + 1);
+        }
+        else if p.y > self.scroll.y + VIEW_HEIGHT - margin_y {
+            self.scroll.y = p.y + margin_y - VIEW_HEIGHT;
+        }
+
+        self.clamp_scroll(level);
+    }
+
+    fn clamp_scroll(&mut self, level: &Level) {
+        let max_x = (level.width() as u16).saturating_sub(VIEW_WIDTH);
+        let max_y = (level.height() as u16).saturating_sub(VIEW_HEIGHT);
+        self.scroll.x = ::std::cmp::min(self.scroll.x, max_x);
+        self.scroll.y = ::std::cmp::min(self.scroll.y, max_y);
     }
 
     pub fn display(&mut self, level: &Level) {
-        for (y, line) in level.layout.iter().enumerate() {
-            let y = y + 2;
-            for (x, tile) in line.iter().enumerate() {
-                let x = x + 2;
-                self.window.put_at(Point::new(x as u16, y as u16), match Level::convert(*tile) {
+        for y in 1..VIEW_HEIGHT + 1 {
+            for x in 1..VIEW_WIDTH + 1 {
+                let p = Point::new(x + self.scroll.x, y + self.scroll.y);
+                let tc = match tile_cell(level.tile(p)) {
                     Some(c) => c,
                     None => ' '.into(),
-                });
+                };
+                self.put_at(p, tc);
             }
         }
 
-        for program in level.programs.iter() {
-            let color = match program.borrow().team {
+        for i in 0..self.trail.len() {
+            let (p, age) = self.trail[i];
+            let glyph = match age {
+                0 => '●',
+                1 => '•',
+                _ => '·',
+            };
+            self.put_at(p, TermCell::new_with_bg(glyph, ColorValue::Red));
+        }
+
+        for program in level.programs.values() {
+            let color = match program.team {
                 Team::Player => ColorValue::Green,
                 Team::Enemy => ColorValue::Red,
             };
-            for (p, tc) in program.borrow().display_color(color) {
-                self.window.put_at(Point::new(p.x + 1, p.y + 1), tc);
+            for (p, tc) in program_cells(program, color) {
+                self.put_at(p, tc);
+            }
+        }
+
+        if let Some(program) = self.highlight.or(self.focus).and_then(|id| level.programs.get(&id)) {
+            for (p, tc) in program_cells(program, ColorValue::Blue) {
+                self.put_at(p, tc);
             }
         }
 
-        if let Some(ref program) = self.highlight {
-            for (p, tc) in program.borrow().display_color(ColorValue::Blue) {
-                self.window.put_at(Point::new(p.x + 1, p.y + 1), tc);
+        // Keep the tile's own glyph, so hazards and the like still show
+        // while planning a move
+        for i in 0..self.reachable.len() {
+            let p = self.reachable[i];
+            if let Some(mut tc) = tile_cell(level.tile(p)) {
+                tc.bg = Some(ColorValue::Blue);
+                self.put_at(p, tc);
             }
         }
 
-        for &(p, c) in self.overlay.iter() {
-            self.window.put_at(Point::new(p.x + 1, p.y + 1), c);
+        for i in 0..self.path.len() {
+            let p = self.path[i];
+            let glyph = if i + 1 == self.path.len() { '◆' } else { '•' };
+            self.put_at(p, TermCell::new_with_bg(glyph, ColorValue::Blue));
+        }
+
+        for i in 0..self.overlay.len() {
+            let (p, c) = self.overlay[i];
+            self.put_at(p, c);
+        }
+
+        for i in 0..self.area_preview.len() {
+            let (p, c) = self.area_preview[i];
+            self.put_at(p, c);
+        }
+
+        for i in 0..self.scores.len() {
+            let (p, c) = self.scores[i];
+            self.put_at(p, c);
         }
 
-        for &(p, c) in self.named_overlay.values() {
-            self.window.put_at(Point::new(p.x + 1, p.y + 1), c);
+        let named: Vec<(Point, TermCell)> = self.named_overlay.values().cloned().collect();
+        for (p, c) in named {
+            self.put_at(p, c);
         }
 
         // TODO:
-        self.window.print_at(Point::new(2, 23), "                                                         ");
-        if let Some(ref help) = self.help {
-            self.window.print_at(Point::new(2, 23), help);
+        self.window.print_at(window::Point::new(2, 23), "                                                         ");
+        if let Some(help) = self.notice.as_ref().or(self.help.as_ref()) {
+            self.window.print_at(window::Point::new(2, 23), help);
         }
     }
 
@@ -90,104 +258,171 @@ impl MapView {
         self.window.refresh(compositor);
     }
 
-    pub fn highlight(&mut self, program: ProgramRef, level: &Level) {
-        self.highlight = Some(program.clone());
+    pub fn highlight(&mut self, program: ProgramId, level: &Level) {
+        self.highlight = Some(program);
         self.update_highlight(level);
     }
 
-    pub fn highlight_range(&mut self, range: usize, level: &Level) {
-        self.highlight_range = Some(range);
+    // Follows an enemy program while it acts, without showing its moves
+    pub fn focus(&mut self, program: ProgramId, level: &Level) {
+        if self.focus != Some(program) {
+            self.trail.clear();
+        }
+        if let Some(position) = level.programs.get(&program).map(|p| p.position) {
+            self.follow(position, level);
+        }
+        self.focus = Some(program);
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.focus = None;
+        self.trail.clear();
+    }
+
+    pub fn add_trail(&mut self, p: Point) {
+        self.trail.push((p, 0));
+    }
+
+    pub fn age_trail(&mut self) {
+        for &mut (_, ref mut age) in self.trail.iter_mut() {
+            *age += 1;
+        }
+        self.trail.retain(|&(_, age)| age < TRAIL_LENGTH);
+    }
+
+    pub fn highlight_range(&mut self, ability: Ability, level: &Level) {
+        self.highlight_range = Some(ability);
         self.update_highlight(level);
     }
 
     pub fn clear_range(&mut self) {
         self.highlight_range = None;
+        self.area_preview.clear();
+    }
+
+    // Shows everything a shaped ability would hit if cast at `target`
+    pub fn preview_area(&mut self, ability: Ability, target: Point, level: &Level) {
+        self.area_preview.clear();
+        if let Some(caster) = self.highlight.and_then(|id| level.programs.get(&id)) {
+            for p in ability.area(caster.position, target) {
+                if !level.in_bounds(p) {
+                    continue;
+                }
+
+                let mut tc: TermCell = if ability.affects(level, caster, p) {
+                    ability.target_glyph().into()
+                }
+                else {
+                    ' '.into()
+                };
+                tc.bg = Some(ColorValue::Red);
+                self.area_preview.push((p, tc));
+            }
+        }
     }
 
     pub fn update_highlight(&mut self, level: &Level) {
-        if let Some(ref program) = self.highlight {
+        if let Some(program) = self.highlight.and_then(|id| level.programs.get(&id)) {
             self.overlay.clear();
-            let position = { program.borrow().position };
+            self.reachable.clear();
+            let position = program.position;
             let Point { x, y } = position;
+            self.follow(position, level);
 
-            if let Some(range) = self.highlight_range {
-                let range = range as isize;
-
-                // TODO: change this to use refactored function
-                for dx in -range..range + 1 {
-                    for dy in -range..range + 1 {
-                        if dx == 0 && dy == 0 {
-                            continue;
-                        }
-                        if dx.abs() + dy.abs() <= range {
-                            let p = Point::new((x as isize + dx) as u16, (y as isize + dy) as u16);
-
-                            // Guard at map edges
-                            if p.x <= 0 || p.y <= 0 {
-                                continue;
-                            }
-
-                            if let Some(tc) = match level.contents_of(p) {
-                                CellContents::Empty => Some('·'.into()),
-                                CellContents::Unpassable | CellContents::Uplink => None,
-                                CellContents::Program(p) => {
-                                    if p.borrow().position != position && p.borrow().team == Team::Enemy {
-                                        Some('X'.into())
-                                    }
-                                    else {
-                                        None
-                                    }
-                                },
-                            } {
-                                let mut tc: TermCell = tc;
-                                tc.bg = Some(ColorValue::Magenta);
-                                self.overlay.push((p, tc));
-                            }
+            if let Some(ability) = self.highlight_range {
+                for p in ability.visible_tiles(level, position) {
+                    let glyph = if ability.valid_target(level, program, p) {
+                        Some(ability.target_glyph())
+                    }
+                    else {
+                        match level.contents_of(p) {
+                            CellContents::Empty => Some('·'),
+                            _ => None,
                         }
+                    };
+
+                    if let Some(glyph) = glyph {
+                        let mut tc: TermCell = glyph.into();
+                        tc.bg = Some(ColorValue::Magenta);
+                        self.overlay.push((p, tc));
                     }
                 }
             }
             else {
-                if !program.borrow().can_move() {
+                if !program.can_move() {
                     return;
                 }
 
-                let east = Point::new(x + 1, y);
-                if level.passable(east) {
-                    self.overlay.push((east, '→'.into()));
-                }
-                let west = Point::new(x - 1, y);
-                if level.passable(west) {
-                    self.overlay.push((west, '←'.into()));
-                }
-                let north = Point::new(x, y - 1);
-                if level.passable(north) {
-                    self.overlay.push((north, '↑'.into()));
-                }
-                let south = Point::new(x, y + 1);
-                if level.passable(south) {
-                    self.overlay.push((south, '↓'.into()));
+                let moves_left = program.moves_left();
+                self.reachable = level.paths(program).reachable();
+                let arrows = [
+                    (Point::new(x + 1, y), '→'),
+                    (Point::new(x - 1, y), '←'),
+                    (Point::new(x, y - 1), '↑'),
+                    (Point::new(x, y + 1), '↓'),
+                ];
+                for &(neighbor, arrow) in arrows.iter() {
+                    if let Some(step) = level.step(position, neighbor) {
+                        if step.cost <= moves_left {
+                            self.overlay.push((neighbor, arrow.into()));
+                        }
+                    }
                 }
             }
         }
     }
 
+    // Plans a multi-step move for the highlighted program
+    pub fn plan_path(&mut self, destination: Point, level: &Level) -> bool {
+        self.path.clear();
+        if let Some(program) = self.highlight.and_then(|id| level.programs.get(&id)) {
+            let paths = level.paths(program);
+            if let Some(path) = paths.path_to(destination) {
+                self.path = path;
+            }
+        }
+        !self.path.is_empty()
+    }
+
+    pub fn next_path_step(&mut self) -> Option<Point> {
+        if self.path.is_empty() {
+            None
+        }
+        else {
+            Some(self.path.remove(0))
+        }
+    }
+
+    pub fn clear_path(&mut self) {
+        self.path.clear();
+    }
+
     pub fn clear_highlight(&mut self) {
         self.highlight = None;
         self.overlay.clear();
+        self.reachable.clear();
+        self.path.clear();
+        self.area_preview.clear();
     }
 
-    pub fn get_highlight(&self) -> Option<ProgramRef> {
-        self.highlight.as_ref().map(Clone::clone)
+    // Marks each scored tile with its rank, 0 being the best; anything
+    // past the tenth gets a dot
+    pub fn show_scores(&mut self, scores: &[(Point, i32)]) {
+        let mut ranked = scores.to_vec();
+        ranked.sort_by(|&(_, s1), &(_, s2)| s2.cmp(&s1));
+        self.scores.clear();
+        for (rank, &(p, _)) in ranked.iter().enumerate() {
+            let glyph = ::std::char::from_digit(rank as u32, 10).unwrap_or('·');
+            self.scores.push((p, TermCell::new_with_bg(glyph, ColorValue::Cyan)));
+        }
     }
 
-    pub fn translate_click(&self, click: Point) -> Option<Point> {
-        for &(point, _) in self.overlay.iter() {
-            if click == point {
-                return Some(point);
-            }
-        }
-        None
+    pub fn clear_scores(&mut self) {
+        self.scores.clear();
+    }
+
+    pub fn get_highlight(&self) -> Option<ProgramId> {
+        self.highlight
     }
 
     pub fn set_help<S: Into<String>>(&mut self, s: S) {
@@ -197,4 +432,12 @@ impl MapView {
     pub fn clear_help(&mut self) {
         self.help = None;
     }
+
+    pub fn notify<S: Into<String>>(&mut self, s: S) {
+        self.notice = Some(s.into());
+    }
+
+    pub fn clear_notice(&mut self) {
+        self.notice = None;
+    }
 }
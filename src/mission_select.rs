@@ -1,6 +1,12 @@
-use voodoo::window::{Point, Window};
+use termion::event::Key;
+use voodoo::color::ColorValue;
+use voodoo::window::{FormattedString, Point, Window};
 
-use game_state::{UiState, ModelView};
+use engine::data;
+use engine::level_file::LevelFile;
+use engine::save_file::SaveFile;
+use game_state::ModelView;
+use player::Player;
 
 const TITLE: [&'static str; 6] = [
     "██████╗ ███████╗    ██╗██╗  ██╗ █████╗  ██████╗██╗  ██╗",
@@ -11,24 +17,69 @@ const TITLE: [&'static str; 6] = [
     "╚═╝  ╚═╝╚══════╝╚═╝    ╚═╝  ╚═╝╚═╝  ╚═╝ ╚═════╝╚═╝  ╚═╝",
 ];
 
+// Where the mission list and the selected mission's details go
+const LIST_X: u16 = 2;
+const LIST_Y: u16 = 3;
+const LIST_LENGTH: usize = 18;
+const DETAIL_X: u16 = 28;
+const DETAIL_WIDTH: usize = 50;
+const BRIEFING_Y: u16 = 5;
+const BRIEFING_LINES: usize = 5;
+const ENEMIES_Y: u16 = 10;
+const PREVIEW_Y: u16 = 12;
+const PREVIEW_HEIGHT: usize = 9;
+const LAUNCH_Y: u16 = 22;
+const LAUNCH_WIDTH: u16 = 18;
+
 pub enum UiEvent {
-    KeyPressed,
+    KeyPressed(Key),
+    Click(Point),
     Tick,
 }
 
 pub enum Transition {
-    Ui(UiState),
+    // Stay on the mission select
+    Ui,
     Level(usize),
+    // Pick up a saved mission where it was left
+    Resume(SaveFile),
+}
+
+enum Screen {
+    Title,
+    List,
+}
+
+// What the mission list shows about a level
+struct Mission {
+    title: String,
+    briefing: Vec<String>,
+    // Each kind of enemy program, with how many there are
+    enemies: Vec<(String, usize)>,
+    preview: Vec<String>,
+    // Why the level file couldn't be read; such missions can't be
+    // launched
+    error: Option<String>,
 }
 
 pub struct State {
     window: Window,
+    screen: Screen,
+    missions: Vec<Mission>,
+    selected: usize,
+    // Shown at the bottom of the screen, e.g. why a save couldn't be
+    // resumed
+    pub error: Option<String>,
 }
 
 impl State {
     pub fn new(window: Window) -> State {
         State {
             window: window,
+            screen: Screen::Title,
+            missions: Vec::new(),
+            selected: 0,
+            error: None,
         }
     }
 }
@@ -39,19 +90,301 @@ impl ::std::fmt::Debug for State {
     }
 }
 
-pub fn next(_state: &mut State, event: UiEvent, _mv: &mut ModelView) -> Transition {
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(::std::mem::replace(&mut line, String::new()));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// Shrinks the layout to fit beside the list. Each glyph stands for a
+// block of tiles and shows the most telling thing in it: an enemy, then
+// an uplink, then any terrain.
+fn preview(file: &LevelFile) -> Vec<String> {
+    let rows: Vec<Vec<char>> = file.layout.iter().map(|row| row.chars().collect()).collect();
+    let height = rows.len();
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let step_x = ::std::cmp::max(1, (width + DETAIL_WIDTH - 1) / DETAIL_WIDTH);
+    let step_y = ::std::cmp::max(1, (height + PREVIEW_HEIGHT - 1) / PREVIEW_HEIGHT);
+    let rank = |c: char| {
+        if file.legend_entry(c).is_some() { 3 }
+        else if c == 'o' { 2 }
+        else if c != ' ' { 1 }
+        else { 0 }
+    };
+
+    let mut result = vec![];
+    for block_y in 0..(height + step_y - 1) / step_y {
+        let mut line = String::new();
+        for block_x in 0..(width + step_x - 1) / step_x {
+            let mut best = ' ';
+            for row in rows.iter().skip(block_y * step_y).take(step_y) {
+                for &c in row.iter().skip(block_x * step_x).take(step_x) {
+                    if rank(c) > rank(best) {
+                        best = c;
+                    }
+                }
+            }
+            line.push(best);
+        }
+        result.push(line);
+    }
+    result
+}
+
+impl Mission {
+    fn from_file(file: &LevelFile) -> Mission {
+        let mut enemies: Vec<(String, usize)> = vec![];
+        for row in file.layout.iter() {
+            for c in row.chars() {
+                if let Some(entry) = file.legend_entry(c) {
+                    match enemies.iter().position(|&(ref name, _)| *name == entry.program) {
+                        Some(index) => enemies[index].1 += 1,
+                        None => enemies.push((entry.program.clone(), 1)),
+                    }
+                }
+            }
+        }
+
+        Mission {
+            title: file.title.clone(),
+            briefing: wrap(&file.briefing.join(" "), DETAIL_WIDTH),
+            enemies: enemies,
+            preview: preview(file),
+            error: None,
+        }
+    }
+}
+
+// Every level in order, up to the first one missing. Levels that fail
+// to load are still listed, with the error as their briefing, but
+// can't be launched.
+fn load_missions() -> Vec<Mission> {
+    let mut missions = vec![];
+    for index in 0.. {
+        match data::load_level_file(index) {
+            Ok(file) => missions.push(Mission::from_file(&file)),
+            Err(data::LoadError::NotFound(_)) => break,
+            Err(err) => missions.push(Mission {
+                title: "(unreadable)".to_owned(),
+                briefing: wrap(&format!("{}", err), DETAIL_WIDTH),
+                enemies: vec![],
+                preview: vec![],
+                error: Some(format!("{}", err)),
+            }),
+        }
+    }
+    missions
+}
+
+fn open_list(state: &mut State, player: &Player) {
+    state.missions = load_missions();
+    state.selected = ::std::cmp::min(player.next_level(), state.missions.len().saturating_sub(1));
+    state.screen = Screen::List;
+    state.error = None;
+}
+
+fn launch(state: &mut State, player: &Player) -> Transition {
+    let error = match state.missions.get(state.selected) {
+        Some(mission) => mission.error.clone(),
+        None => return Transition::Ui,
+    };
+    if let Some(error) = error {
+        state.error = Some(format!("Could not load level: {}", error));
+        Transition::Ui
+    }
+    else if player.unlocked(state.selected) {
+        Transition::Level(state.selected)
+    }
+    else {
+        state.error = Some("Clear the mission before it to unlock this one".to_owned());
+        Transition::Ui
+    }
+}
+
+fn select(state: &mut State, index: usize) {
+    if index < state.missions.len() && index != state.selected {
+        state.selected = index;
+        state.error = None;
+    }
+}
+
+fn next_title(state: &mut State, event: UiEvent, mv: &mut ModelView) -> Transition {
+    use self::UiEvent::*;
+    match event {
+        KeyPressed(Key::Left) => {
+            mv.ai.difficulty = mv.ai.difficulty.easier();
+            Transition::Ui
+        }
+        KeyPressed(Key::Right) => {
+            mv.ai.difficulty = mv.ai.difficulty.harder();
+            Transition::Ui
+        }
+        KeyPressed(Key::Char('c')) if data::has_saved_mission() => {
+            match data::load_mission() {
+                Ok(save) => Transition::Resume(save),
+                Err(err) => {
+                    state.error = Some(format!("{}", err));
+                    Transition::Ui
+                }
+            }
+        }
+        KeyPressed(_) | Click(_) => {
+            open_list(state, &mv.player);
+            Transition::Ui
+        }
+        Tick => Transition::Ui,
+    }
+}
+
+fn next_list(state: &mut State, event: UiEvent, mv: &mut ModelView) -> Transition {
     use self::UiEvent::*;
     match event {
-        KeyPressed => Transition::Level(0),
-        Tick => Transition::Ui(UiState::Unselected),
+        KeyPressed(Key::Up) | KeyPressed(Key::Char('w')) => {
+            let index = state.selected.saturating_sub(1);
+            select(state, index);
+        }
+        KeyPressed(Key::Down) | KeyPressed(Key::Char('s')) => {
+            let index = state.selected + 1;
+            select(state, index);
+        }
+        KeyPressed(Key::Left) => mv.ai.difficulty = mv.ai.difficulty.easier(),
+        KeyPressed(Key::Right) => mv.ai.difficulty = mv.ai.difficulty.harder(),
+        KeyPressed(Key::Char('\n')) | KeyPressed(Key::Char(' ')) => return launch(state, &mv.player),
+        KeyPressed(Key::Esc) | KeyPressed(Key::Backspace) => {
+            state.screen = Screen::Title;
+            state.error = None;
+        }
+        Click(p) => {
+            if p.x >= LIST_X && p.x < DETAIL_X && p.y >= LIST_Y && p.y < LIST_Y + LIST_LENGTH as u16 {
+                // Clicking the selected mission again launches it
+                let index = (p.y - LIST_Y) as usize;
+                if index == state.selected {
+                    return launch(state, &mv.player);
+                }
+                select(state, index);
+            }
+            else if p.x >= DETAIL_X && p.x < DETAIL_X + LAUNCH_WIDTH && p.y == LAUNCH_Y {
+                return launch(state, &mv.player);
+            }
+        }
+        KeyPressed(_) | Tick => {}
     }
+    Transition::Ui
 }
 
-pub fn display(mission_state: &mut State, compositor: &mut ::voodoo::compositor::Compositor, _mv: &mut ModelView) {
+pub fn next(state: &mut State, event: UiEvent, mv: &mut ModelView) -> Transition {
+    match state.screen {
+        Screen::Title => next_title(state, event, mv),
+        Screen::List => next_list(state, event, mv),
+    }
+}
+
+fn display_title(mission_state: &mut State, mv: &ModelView) {
     for (offset, line) in TITLE.iter().enumerate() {
         mission_state.window.print_at(Point::new(13, 6 + offset as u16), *line);
     }
-    mission_state.window.print_at(Point::new(30, 14), "PRESS ANY KEY TO BEGIN");
+    if mv.player.completed.is_empty() {
+        mission_state.window.print_at(Point::new(30, 14), "PRESS ANY KEY TO BEGIN");
+    }
+    else {
+        mission_state.window.print_at(Point::new(29, 14), "PRESS ANY KEY TO CONTINUE");
+        let stats = mv.player.stats;
+        let record = format!("LEVELS CLEARED {}  WON {}  LOST {}  KILLS {}",
+                             mv.player.completed.len(), stats.missions_won, stats.missions_lost, stats.enemies_destroyed);
+        mission_state.window.print_at(Point::new(2, 3), &format!("{:^76.76}", record));
+    }
     mission_state.window.print_at(Point::new(33, 15), "PRESS Q TO QUIT");
+    mission_state.window.print_at(Point::new(29, 17), &format!("DIFFICULTY: < {:^6} >", mv.ai.difficulty.name()));
+    mission_state.window.print_at(Point::new(29, 18), "LEFT/RIGHT TO CHANGE");
+    if data::has_saved_mission() {
+        mission_state.window.print_at(Point::new(25, 20), "PRESS C TO CONTINUE SAVED MISSION");
+    }
+}
+
+fn display_list(state: &mut State, mv: &ModelView) {
+    let window = &mut state.window;
+    window.print_at(Point::new(LIST_X, 1), "SELECT MISSION");
+    window.print_at(Point::new(DETAIL_X, 1), &format!("DIFFICULTY: < {:^6} >", mv.ai.difficulty.name()));
+
+    for (index, mission) in state.missions.iter().enumerate().take(LIST_LENGTH) {
+        let status = if mission.error.is_some() {
+            "ERR"
+        }
+        else if mv.player.completed.contains(&index) {
+            "DONE"
+        }
+        else if mv.player.unlocked(index) {
+            ""
+        }
+        else {
+            "LOCK"
+        };
+        let mut f: FormattedString = (&format!("{:02} {:<16.16} {:>4}", index, mission.title, status)).into();
+        f.bg = if index == state.selected {
+            Some(ColorValue::Red)
+        }
+        else if mv.player.unlocked(index) {
+            Some(ColorValue::Magenta)
+        }
+        else {
+            None
+        };
+        window.print_at(Point::new(LIST_X, LIST_Y + index as u16), f);
+    }
+
+    if let Some(mission) = state.missions.get(state.selected) {
+        window.print_at(Point::new(DETAIL_X, LIST_Y), &format!("{:.50}", mission.title));
+        for (offset, line) in mission.briefing.iter().enumerate().take(BRIEFING_LINES) {
+            window.print_at(Point::new(DETAIL_X, BRIEFING_Y + offset as u16), line);
+        }
+
+        let enemies: Vec<String> = mission.enemies.iter().map(|&(ref name, count)| {
+            if count == 1 { name.clone() } else { format!("{} x{}", name, count) }
+        }).collect();
+        let enemies = if enemies.is_empty() { "none".to_owned() } else { enemies.join(", ") };
+        window.print_at(Point::new(DETAIL_X, ENEMIES_Y), &format!("{:.50}", format!("HOSTILES: {}", enemies)));
+
+        for (offset, line) in mission.preview.iter().enumerate().take(PREVIEW_HEIGHT) {
+            window.print_at(Point::new(DETAIL_X, PREVIEW_Y + offset as u16), line);
+        }
+
+        let launch = if mission.error.is_some() {
+            "    Unreadable    "
+        }
+        else if mv.player.unlocked(state.selected) {
+            ">     Launch     <"
+        }
+        else {
+            "      Locked      "
+        };
+        let mut f: FormattedString = launch.into();
+        f.bg = Some(ColorValue::Magenta);
+        window.print_at(Point::new(DETAIL_X, LAUNCH_Y), f);
+    }
+
+    window.print_at(Point::new(LIST_X, 23), "UP/DOWN OR CLICK TO SELECT, ENTER TO LAUNCH, ESC TO GO BACK");
+}
+
+pub fn display(mission_state: &mut State, compositor: &mut ::voodoo::compositor::Compositor, mv: &mut ModelView) {
+    mission_state.window.clear();
+    match mission_state.screen {
+        Screen::Title => display_title(mission_state, mv),
+        Screen::List => display_list(mission_state, mv),
+    }
+    if let Some(ref error) = mission_state.error {
+        mission_state.window.print_at(Point::new(2, 21), &format!("{:^76.76}", error));
+    }
     mission_state.window.refresh(compositor);
 }
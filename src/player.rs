@@ -1,8 +1,20 @@
-use program::Program;
+use engine::program::Program;
+
+// Totals over the whole campaign
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Stats {
+    pub missions_won: usize,
+    pub missions_lost: usize,
+    pub enemies_destroyed: usize,
+    pub programs_lost: usize,
+}
 
 pub struct Player {
     pub name: String,
     pub programs: Vec<Program>,
+    // Indices of the levels won at least once
+    pub completed: Vec<usize>,
+    pub stats: Stats,
 }
 
 impl Player {
@@ -10,6 +22,25 @@ impl Player {
         Player {
             name: name.into(),
             programs: Vec::new(),
+            completed: Vec::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn complete(&mut self, level_index: usize) {
+        if !self.completed.contains(&level_index) {
+            self.completed.push(level_index);
+            self.completed.sort();
         }
     }
+
+    // Levels open up one at a time, as the one before is won
+    pub fn unlocked(&self, level_index: usize) -> bool {
+        level_index == 0 || self.completed.contains(&level_index) || self.completed.contains(&(level_index - 1))
+    }
+
+    // The first level not yet won, where the campaign carries on from
+    pub fn next_level(&self) -> usize {
+        (0..).find(|index| !self.completed.contains(index)).unwrap()
+    }
 }
@@ -1,21 +1,153 @@
 use voodoo::color::ColorValue;
-use voodoo::window::{Point, TermCell};
+use voodoo::window::{self, TermCell};
 
+use engine::Point;
+use engine::action::Action;
+use engine::level::{CellContents, Level, MoveSnapshot};
+use engine::program::{Ability, ProgramId, Shape, Team};
 use game_state::{self, UiEvent, UiState, ModelView};
 use info_view::InfoView;
 use map_view::MapView;
-use level::{CellContents, Level};
-use player::Player;
-use program::{Ability, Team};
+
+const SELECTED_HELP: &'static str = "Click arrows or a highlighted tile to move; click ability at left to use";
+
+// Moves the program one step, remembering where it was
+fn step(program: ProgramId, to: Point, level: &mut Level, undo: &mut Vec<MoveSnapshot>) -> bool {
+    let before = level.snapshot_move(program);
+    if Action::Move(program, to).apply(level).is_ok() {
+        undo.extend(before);
+        true
+    }
+    else {
+        false
+    }
+}
+
+fn select_target(ability: Ability, level: &Level, map: &mut MapView) {
+    map.set_help(format!("Select target. {}", ability.describe()));
+    map.highlight_range(ability, level);
+}
+
+fn aim(ability: Ability, click: Point, level: &mut Level, map: &mut MapView, info: &mut InfoView, undo: &mut Vec<MoveSnapshot>) -> UiState {
+    let valid = match map.get_highlight() {
+        Some(caster) => ability.valid_target(level, &level.programs[&caster], click),
+        None => false,
+    };
+
+    if !valid {
+        info.clear_ability();
+        map.clear_range();
+        map.update_highlight(level);
+        UiState::Selected
+    }
+    else if ability.shape == Shape::Single {
+        use_ability(ability, click, level, map, info, undo)
+    }
+    else {
+        map.preview_area(ability, click, level);
+        map.set_help("Click the target again to confirm");
+        UiState::ConfirmTarget(ability, click)
+    }
+}
+
+fn use_ability(ability: Ability, target: Point, level: &mut Level, map: &mut MapView, info: &mut InfoView, undo: &mut Vec<MoveSnapshot>) -> UiState {
+    info.clear_ability();
+
+    let mut result = UiState::Selected;
+    if let Some(caster) = map.get_highlight() {
+        match Action::UseAbility(caster, ability, target).apply(level) {
+            Ok(_) => {
+                // Abilities can't be taken back, and neither can the
+                // moves that set them up
+                undo.clear();
+                info.clear();
+                info.display_program(&level.programs[&caster]);
+                result = UiState::Animating;
+            }
+            Err(err) => map.set_help(format!("Can't use that: {}", err)),
+        }
+    }
+
+    map.clear_range();
+    map.update_highlight(level);
+    result
+}
+
+fn click_selected(click: Point, level: &mut Level, map: &mut MapView, info: &mut InfoView, undo: &mut Vec<MoveSnapshot>) -> UiState {
+    if let Some(program) = map.get_highlight() {
+        if step(program, click, level, undo) {
+            info.update_program(&level.programs[&program]);
+            map.update_highlight(&level);
+            return UiState::Selected;
+        }
+    }
+
+    if map.plan_path(click, level) {
+        map.set_help("Click the destination again to move there");
+        UiState::PlannedMove(click)
+    }
+    else {
+        map.clear_highlight();
+        info.clear();
+        select_program(click, level, map, info)
+    }
+}
+
+// Takes the next step along the planned path; false once there is
+// nothing left to do
+fn follow_path(level: &mut Level, map: &mut MapView, info: &mut InfoView) -> bool {
+    let program = match map.get_highlight() {
+        Some(program) => program,
+        None => return false,
+    };
+    let next = match map.next_path_step() {
+        Some(next) => next,
+        None => return false,
+    };
+
+    if Action::Move(program, next).apply(level).is_err() {
+        return false;
+    }
+
+    info.update_program(&level.programs[&program]);
+    map.update_highlight(level);
+    true
+}
+
+// Takes back the last move made this turn
+fn undo_move(state: UiState, undo: &mut Vec<MoveSnapshot>, level: &mut Level, map: &mut MapView, info: &mut InfoView) -> UiState {
+    let last = match undo.pop() {
+        Some(last) => last,
+        None => {
+            map.notify("Nothing to undo this turn");
+            return state;
+        }
+    };
+    let program = last.program();
+    if let Err(err) = level.undo_move(last) {
+        map.notify(format!("Can't undo: {}", err));
+        return state;
+    }
+
+    map.clear_path();
+    map.clear_range();
+    info.clear_ability();
+    info.clear();
+    info.display_program(&level.programs[&program]);
+    map.highlight(program, level);
+    map.set_help(SELECTED_HELP);
+    map.notify("Move undone");
+    UiState::Selected
+}
 
 fn select_program(point: Point, level: &Level, map: &mut MapView, info: &mut InfoView) -> UiState {
     use game_state::UiState::*;
 
-    for program in level.programs.iter() {
-        if program.borrow().intersects(point) && program.borrow().team == Team::Player {
-            map.highlight(program.clone(), &level);
-            info.display_program(&program.borrow());
-            map.set_help("Click arrows to move; click ability at left to use");
+    for (&id, program) in level.programs.iter() {
+        if program.intersects(point) && program.team == Team::Player {
+            map.highlight(id, &level);
+            info.display_program(program);
+            map.set_help(SELECTED_HELP);
             return Selected;
         }
     }
@@ -26,33 +158,38 @@ pub fn next(state: UiState, event: UiEvent, mv: &mut ModelView) -> UiState {
     use game_state::UiEvent::*;
     use game_state::UiState::*;
 
-    let ModelView { ref mut info, ref mut map, ref mut player, ref mut level, .. } = *mv;
+    let ModelView { ref mut info, ref mut map, ref mut player, ref mut level, ref mut undo, .. } = *mv;
 
     let result = match (state, event) {
         (Unselected, ClickMap(p)) => {
             select_program(p, level, map, info)
         }
         (Selected, ClickMap(p)) => {
-            let result = map.translate_click(p);
-            if let Some(p) = result {
-                if let Some(ref mut program) = map.get_highlight() {
-                    program.borrow_mut().move_to(p);
-                    info.update_program(&program.borrow());
+            click_selected(p, level, map, info, undo)
+        }
+        (PlannedMove(destination), ClickMap(p)) => {
+            if p == destination {
+                // The whole path is taken back at once
+                if let Some(program) = map.get_highlight() {
+                    undo.extend(level.snapshot_move(program));
                 }
-                map.update_highlight(&level);
-                Selected
+                map.set_help("Moving...");
+                Moving
             }
             else {
-                map.clear_highlight();
-                info.clear();
-                select_program(p, level, map, info)
+                map.clear_path();
+                click_selected(p, level, map, info, undo)
             }
         }
-        (Selected, Move(d)) => {
-            use game_state::Direction::*;
-            if let Some(ref mut program) = map.get_highlight() {
-                let cur = { program.borrow().position };
-                let can_move = { program.borrow().can_move() };
+        (Selected, Move(d)) | (PlannedMove(_), Move(d)) => {
+            use engine::Direction::*;
+            // Stepping by hand drops any path waiting for confirmation
+            if let PlannedMove(_) = state {
+                map.clear_path();
+                map.set_help(SELECTED_HELP);
+            }
+            if let Some(program) = map.get_highlight() {
+                let cur = level.programs[&program].position;
                 let point = match d {
                     Up => Point::new(cur.x, cur.y - 1),
                     Down => Point::new(cur.x, cur.y + 1),
@@ -60,66 +197,40 @@ pub fn next(state: UiState, event: UiEvent, mv: &mut ModelView) -> UiState {
                     Right => Point::new(cur.x + 1, cur.y),
                 };
 
-                if can_move {
-                    if let CellContents::Empty = level.contents_of(point) {
-                        program.borrow_mut().move_to(point);
-                        info.update_program(&program.borrow());
-                        map.update_highlight(&level);
-                    }
+                if step(program, point, level, undo) {
+                    info.update_program(&level.programs[&program]);
+                    map.update_highlight(&level);
                 }
             }
-            state
+            Selected
         }
         (_, Move(_)) => {
             state
         }
         (Unselected, ClickInfo(_)) => Unselected,
-        (Selected, ClickInfo(p)) => {
+        (Selected, ClickInfo(p)) | (PlannedMove(_), ClickInfo(p)) => {
+            map.clear_path();
             if let Some(ability) = info.translate_click(p) {
-                match ability {
-                    Ability::Destroy { damage, range } => {
-                        map.set_help(format!("Select target. Damage: 0x{:x} Range: 0x{:x}", damage, range));
-                        map.highlight_range(range, level);
-                    }
-                }
+                select_target(ability, level, map);
                 return SelectTarget(ability);
             }
             Selected
         }
         (SelectTarget(ability), ClickMap(p)) => {
-            let result = map.translate_click(p);
-            info.clear_ability();
-
-            map.clear_range();
-            map.update_highlight(level);
-            if let Some(p) = result {
-                match level.contents_of(p) {
-                    CellContents::Program(p) => {
-                        ability.apply(&mut p.borrow_mut());
-                        if let Some(caster) = map.get_highlight() {
-                            caster.borrow_mut().turn_state.ability_used = true;
-                            info.clear();
-                            info.display_program(&caster.borrow());
-                        }
-                        Animating
-                    },
-                    _ => Selected,
-                }
+            aim(ability, p, level, map, info, undo)
+        }
+        (ConfirmTarget(ability, target), ClickMap(p)) => {
+            if p == target {
+                use_ability(ability, target, level, map, info, undo)
             }
             else {
-                Selected
+                aim(ability, p, level, map, info, undo)
             }
         }
-        (SelectTarget(_), ClickInfo(p)) => {
+        (SelectTarget(_), ClickInfo(p)) | (ConfirmTarget(..), ClickInfo(p)) => {
             let result = info.translate_click(p);
             if let Some(ability) = result {
-                // TODO: refactor this out
-                match ability {
-                    Ability::Destroy { damage, range } => {
-                        map.set_help(format!("Select target. Damage: 0x{:x} Range: 0x{:x}", damage, range));
-                        map.highlight_range(range, level);
-                    }
-                }
+                select_target(ability, level, map);
                 SelectTarget(ability)
             }
             else {
@@ -130,7 +241,7 @@ pub fn next(state: UiState, event: UiEvent, mv: &mut ModelView) -> UiState {
             }
         }
         (state, Tick) => {
-            let modified = game_state::update_programs(level, map);
+            let modified = game_state::update_programs(level, map, &mut player.stats);
 
             match state {
                 Animating => {
@@ -147,11 +258,28 @@ pub fn next(state: UiState, event: UiEvent, mv: &mut ModelView) -> UiState {
                         state
                     }
                 }
+                Moving => {
+                    if follow_path(level, map, info) {
+                        Moving
+                    }
+                    else {
+                        // Nothing to take back if the path was blocked
+                        // from the start
+                        if undo.last().map_or(false, |last| !level.moved_since(last)) {
+                            undo.pop();
+                        }
+                        map.clear_path();
+                        map.set_help(SELECTED_HELP);
+                        Selected
+                    }
+                }
                 _ => state,
             }
         }
         (Animating, _) => Animating,
-        (state, Quit) | (state, EndTurn) => { state },
+        (Moving, _) => Moving,
+        (state, Undo) => undo_move(state, undo, level, map, info),
+        (state, Quit) | (state, EndTurn) | (state, Scroll(_)) | (state, ToggleDebug) | (state, FastForward) | (state, Save) => { state },
     };
 
     if let Unselected = result {
@@ -169,6 +297,8 @@ pub fn next_setup(state: UiState, event: UiEvent, mv: &mut ModelView) -> UiState
         (state, Quit) => state,
         (state, Tick) => state,
         (state, Move(_)) => state,
+        (state, Scroll(_)) => state,
+        (state, ToggleDebug) | (state, FastForward) | (state, Save) | (state, Undo) => state,
 
         (Unselected, ClickMap(p)) | (Selected, ClickMap(p)) => {
             match mv.level.contents_of(p) {
@@ -191,33 +321,34 @@ pub fn next_setup(state: UiState, event: UiEvent, mv: &mut ModelView) -> UiState
         },
         (Unselected, ClickInfo(_)) => Unselected,
         (Selected, ClickInfo(p)) => {
-            if let Some(ref program) = mv.program_list.handle_click(p) {
-                let mut p = (*program).clone();
+            let program = mv.program_list.handle_click(p).cloned();
+            if let Some(program) = program {
                 let uplink = mv.map.get_overlay().get("uplink").unwrap().0;
-                p.position = uplink;
-                mv.level.remove_uplink_at(uplink);
-                mv.level.add_program(p);
-            }
-            if let Some(idx) = mv.program_list.get_selection_index() {
-                mv.program_list.choices().remove(idx as usize);
+                if Action::Deploy(program, uplink).apply(&mut mv.level).is_ok() {
+                    if let Some(idx) = mv.program_list.get_selection_index() {
+                        mv.program_list.choices().remove(idx as usize);
+                    }
+                }
             }
             mv.program_list.clear_selection();
 
             Unselected
         },
 
-        (SelectTarget(_), _) | (Animating, _) | (_, EndTurn) => unreachable!(),
+        (SelectTarget(_), _) | (ConfirmTarget(..), _) | (PlannedMove(_), _) |
+        (Moving, _) | (Animating, _) | (_, EndTurn) => unreachable!(),
     };
 
     match new_state {
         Unselected => {
             mv.map.get_overlay().remove("uplink");
             mv.info.clear();
+            mv.info.window.print_at(window::Point::new(2, 2), &mv.level.title);
             mv.map.set_help("Choose uplink Θ to load program")
         },
         Selected => {
             mv.program_list.display(&mut mv.info.window);
-            mv.info.window.print_at(Point::new(2, 2), "Programs:");
+            mv.info.window.print_at(window::Point::new(2, 2), "Programs:");
             mv.map.set_help("Choose program to load at left")
         },
         _ => unreachable!(),
@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::io::{self, Write};
+
+use engine::data::{self, LoadError};
+use engine::level_file::ParseError;
+use engine::program::Team;
+use engine::save_file;
+use player::{Player, Stats};
+
+// The campaign profile is saved in the same style as a saved mission
+// (see `engine::save_file`):
+//
+//     version: 1
+//     name: David
+//     completed: 0 1
+//     won: 2
+//     lost: 1
+//     destroyed: 9
+//     lost_programs: 3
+//
+//     roster:
+//     name: Hack 1
+//     ...
+//
+// with one `roster:` block per program the player owns.
+
+pub const VERSION: usize = 1;
+const PROFILE_PATH: &'static str = "campaign.profile";
+
+pub fn write(player: &Player) -> String {
+    let completed: Vec<String> = player.completed.iter().map(|index| index.to_string()).collect();
+    let mut out = String::new();
+    out.push_str(&format!("version: {}\n", VERSION));
+    out.push_str(&format!("name: {}\n", player.name));
+    out.push_str(&format!("completed: {}\n", completed.join(" ")));
+    out.push_str(&format!("won: {}\n", player.stats.missions_won));
+    out.push_str(&format!("lost: {}\n", player.stats.missions_lost));
+    out.push_str(&format!("destroyed: {}\n", player.stats.enemies_destroyed));
+    out.push_str(&format!("lost_programs: {}\n", player.stats.programs_lost));
+    for program in player.programs.iter() {
+        out.push_str("\nroster:\n");
+        save_file::write_program(&mut out, program);
+    }
+    out
+}
+
+pub fn parse(source: &str) -> Result<Player, ParseError> {
+    let mut player = Player::new("");
+    let mut stats = Stats::default();
+
+    for entry in try!(save_file::read_entries(source, "profile", VERSION)) {
+        let (key, value) = (entry.key, entry.value);
+        let bad_value = || entry.bad_value();
+
+        if key == "roster" {
+            player.programs.push(save_file::blank_program());
+            continue;
+        }
+
+        // Once the roster starts, every key belongs to its last program
+        if let Some(program) = player.programs.last_mut() {
+            if !save_file::read_program_key(program, key, value) {
+                return Err(bad_value());
+            }
+            continue;
+        }
+
+        match key {
+            "name" => player.name = value.to_owned(),
+            "completed" => {
+                for index in value.split_whitespace() {
+                    player.complete(try!(index.parse().map_err(|_| bad_value())));
+                }
+            }
+            "won" => stats.missions_won = try!(value.parse().map_err(|_| bad_value())),
+            "lost" => stats.missions_lost = try!(value.parse().map_err(|_| bad_value())),
+            "destroyed" => stats.enemies_destroyed = try!(value.parse().map_err(|_| bad_value())),
+            "lost_programs" => stats.programs_lost = try!(value.parse().map_err(|_| bad_value())),
+            _ => return Err(entry.unknown_key()),
+        }
+    }
+
+    if player.programs.iter().any(|program| program.name.is_empty() || program.team != Team::Player) {
+        return Err(ParseError::new(source.lines().count() + 1, 1, "roster programs need a name and the player team"));
+    }
+    player.stats = stats;
+    Ok(player)
+}
+
+// None if there is no profile yet
+pub fn load() -> Result<Option<Player>, LoadError> {
+    let source = match data::read_source(PROFILE_PATH) {
+        Ok(source) => source,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(LoadError::Io(PROFILE_PATH.to_owned(), err)),
+    };
+    match parse(&source) {
+        Ok(player) => Ok(Some(player)),
+        Err(err) => Err(LoadError::Parse(PROFILE_PATH.to_owned(), err)),
+    }
+}
+
+pub fn save(player: &Player) -> Result<(), LoadError> {
+    let result = File::create(PROFILE_PATH).and_then(|mut file| file.write_all(write(player).as_bytes()));
+    result.map_err(|err| LoadError::Io(PROFILE_PATH.to_owned(), err))
+}
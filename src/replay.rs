@@ -0,0 +1,366 @@
+use std::fs::File;
+use std::io::Write;
+
+use termion::event::{Event, Key, MouseButton, MouseEvent};
+
+use engine::data::{self, LoadError};
+use engine::level_file::{self, ParseError};
+use engine::save_file::{self, SaveFile};
+
+// Replays are plain text:
+//
+//     version: 1
+//     level: 0
+//
+//     inputs:
+//     key char:97
+//     click left 34 10
+//     tick 12
+//
+//     mission:
+//     version: 1
+//     ...
+//
+// `inputs:` lists everything the main loop fed the game, in order, with
+// runs of ticks counted. Everything after `mission:` is the mission as it
+// stood when recording started, in the `save_file` format; `level` has
+// to agree with it. Nothing in the game is random yet, so playing the
+// inputs back against the mission reproduces it exactly.
+
+pub const VERSION: usize = 1;
+const REPLAY_PATH: &'static str = "last.replay";
+// Playback speeds, in replay ticks per real tick
+const SPEEDS: [usize; 4] = [1, 2, 4, 8];
+
+#[derive(Clone,Debug)]
+pub enum Input {
+    Event(Event),
+    Tick,
+}
+
+// A mission being recorded
+pub struct Recording {
+    level_index: usize,
+    // The mission when recording started, already written out
+    mission: String,
+    inputs: Vec<Input>,
+}
+
+pub struct Replay {
+    pub mission: SaveFile,
+    pub inputs: Vec<Input>,
+}
+
+pub struct Playback {
+    inputs: Vec<Input>,
+    // The next input to feed the game
+    position: usize,
+    // Ticks played so far
+    ticks: usize,
+    pub paused: bool,
+    speed: usize,
+    // Ticks to play while paused
+    steps: usize,
+}
+
+impl Recording {
+    pub fn new(start: &SaveFile) -> Recording {
+        Recording {
+            level_index: start.level_index,
+            mission: save_file::write(start),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, input: Input) {
+        self.inputs.push(input);
+    }
+}
+
+fn button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "left",
+        MouseButton::Right => "right",
+        MouseButton::Middle => "middle",
+        MouseButton::WheelUp => "wheelup",
+        MouseButton::WheelDown => "wheeldown",
+    }
+}
+
+fn parse_button(name: &str) -> Option<MouseButton> {
+    match name {
+        "left" => Some(MouseButton::Left),
+        "right" => Some(MouseButton::Right),
+        "middle" => Some(MouseButton::Middle),
+        "wheelup" => Some(MouseButton::WheelUp),
+        "wheeldown" => Some(MouseButton::WheelDown),
+        _ => None,
+    }
+}
+
+fn key_token(key: Key) -> Option<String> {
+    Some(match key {
+        Key::Char(c) => format!("char:{}", c as u32),
+        Key::Alt(c) => format!("alt:{}", c as u32),
+        Key::Ctrl(c) => format!("ctrl:{}", c as u32),
+        Key::F(n) => format!("f:{}", n),
+        Key::Backspace => "backspace".to_owned(),
+        Key::Left => "left".to_owned(),
+        Key::Right => "right".to_owned(),
+        Key::Up => "up".to_owned(),
+        Key::Down => "down".to_owned(),
+        Key::Home => "home".to_owned(),
+        Key::End => "end".to_owned(),
+        Key::PageUp => "pageup".to_owned(),
+        Key::PageDown => "pagedown".to_owned(),
+        Key::Delete => "delete".to_owned(),
+        Key::Insert => "insert".to_owned(),
+        Key::Esc => "esc".to_owned(),
+        _ => return None,
+    })
+}
+
+fn parse_key(token: &str) -> Option<Key> {
+    let character = |code: &str| code.parse().ok().and_then(::std::char::from_u32);
+    let (name, argument) = match token.find(':') {
+        Some(split) => (&token[..split], &token[split + 1..]),
+        None => (token, ""),
+    };
+    match name {
+        "char" => character(argument).map(Key::Char),
+        "alt" => character(argument).map(Key::Alt),
+        "ctrl" => character(argument).map(Key::Ctrl),
+        "f" => argument.parse().ok().map(Key::F),
+        "backspace" => Some(Key::Backspace),
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "home" => Some(Key::Home),
+        "end" => Some(Key::End),
+        "pageup" => Some(Key::PageUp),
+        "pagedown" => Some(Key::PageDown),
+        "delete" => Some(Key::Delete),
+        "insert" => Some(Key::Insert),
+        "esc" => Some(Key::Esc),
+        _ => None,
+    }
+}
+
+// One line of the `inputs:` section; None for events the game ignores,
+// which aren't recorded
+fn event_line(event: &Event) -> Option<String> {
+    match *event {
+        Event::Key(key) => key_token(key).map(|token| format!("key {}", token)),
+        Event::Mouse(MouseEvent::Press(button, x, y)) => Some(format!("click {} {} {}", button_name(button), x, y)),
+        _ => None,
+    }
+}
+
+fn parse_input(line: &str) -> Option<Vec<Input>> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    match (tokens[0], tokens.len()) {
+        ("tick", 2) => tokens[1].parse().ok().map(|count| vec![Input::Tick; count]),
+        ("key", 2) => parse_key(tokens[1]).map(|key| vec![Input::Event(Event::Key(key))]),
+        ("click", 4) => match (parse_button(tokens[1]), tokens[2].parse(), tokens[3].parse()) {
+            (Some(button), Ok(x), Ok(y)) => Some(vec![Input::Event(Event::Mouse(MouseEvent::Press(button, x, y)))]),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn write(recording: &Recording) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("version: {}\n", VERSION));
+    out.push_str(&format!("level: {}\n", recording.level_index));
+
+    out.push_str("\ninputs:\n");
+    let mut ticks = 0;
+    for input in recording.inputs.iter() {
+        let line = match *input {
+            Input::Tick => {
+                ticks += 1;
+                continue;
+            }
+            Input::Event(ref event) => match event_line(event) {
+                Some(line) => line,
+                None => continue,
+            },
+        };
+        if ticks > 0 {
+            out.push_str(&format!("tick {}\n", ticks));
+            ticks = 0;
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    if ticks > 0 {
+        out.push_str(&format!("tick {}\n", ticks));
+    }
+
+    out.push_str("\nmission:\n");
+    out.push_str(&recording.mission);
+    out
+}
+
+pub fn parse(source: &str) -> Result<Replay, ParseError> {
+    let mut version = None;
+    let mut level_index = None;
+    let mut inputs = vec![];
+    let mut in_inputs = false;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let column = level_file::column_of(line, trimmed);
+
+        match trimmed {
+            "inputs:" => {
+                in_inputs = true;
+                continue;
+            }
+            "mission:" => {
+                if version.is_none() {
+                    return Err(ParseError::new(line_number, column, "missing 'version'"));
+                }
+                let level_index = match level_index {
+                    Some(level_index) => level_index,
+                    None => return Err(ParseError::new(line_number, column, "missing 'level'")),
+                };
+
+                // The rest of the file is a saved mission, so its errors
+                // are moved down to where it starts
+                let rest: Vec<&str> = source.lines().skip(line_number).collect();
+                let mission = match save_file::parse(&rest.join("\n")) {
+                    Ok(mission) => mission,
+                    Err(err) => return Err(ParseError::new(err.line + line_number, err.column, err.message)),
+                };
+                if mission.level_index != level_index {
+                    return Err(ParseError::new(line_number, column, format!("mission is for level {}, not level {}", mission.level_index, level_index)));
+                }
+                return Ok(Replay {
+                    mission: mission,
+                    inputs: inputs,
+                });
+            }
+            _ => {}
+        }
+
+        if in_inputs {
+            match parse_input(trimmed) {
+                Some(parsed) => inputs.extend(parsed),
+                None => return Err(ParseError::new(line_number, column, format!("unknown input '{}'", trimmed))),
+            }
+            continue;
+        }
+
+        let split = match trimmed.find(':') {
+            Some(split) => split,
+            None => return Err(ParseError::new(line_number, column, "expected 'key: value'")),
+        };
+        let key = trimmed[..split].trim();
+        let value = trimmed[split + 1..].trim();
+        let bad_value = || ParseError::new(line_number, column, format!("bad value for '{}'", key));
+        match key {
+            "version" => match value.parse() {
+                Ok(VERSION) => version = Some(VERSION),
+                Ok(other) => return Err(ParseError::new(line_number, column, format!("unsupported replay version {}, expected {}", other, VERSION))),
+                Err(_) => return Err(bad_value()),
+            },
+            "level" => level_index = Some(try!(value.parse().map_err(|_| bad_value()))),
+            _ => return Err(ParseError::new(line_number, column, format!("unknown key '{}'", key))),
+        }
+    }
+
+    Err(ParseError::new(source.lines().count() + 1, 1, "missing 'mission:' section"))
+}
+
+// Writes the recording over the last one
+pub fn save(recording: &Recording) -> Result<(), LoadError> {
+    let result = File::create(REPLAY_PATH).and_then(|mut file| file.write_all(write(recording).as_bytes()));
+    result.map_err(|err| LoadError::Io(REPLAY_PATH.to_owned(), err))
+}
+
+pub fn load(path: &str) -> Result<Replay, LoadError> {
+    let source = match data::read_source(path) {
+        Ok(source) => source,
+        Err(err) => return Err(LoadError::Io(path.to_owned(), err)),
+    };
+    parse(&source).map_err(|err| LoadError::Parse(path.to_owned(), err))
+}
+
+impl Playback {
+    pub fn new(inputs: Vec<Input>) -> Playback {
+        Playback {
+            inputs: inputs,
+            position: 0,
+            ticks: 0,
+            paused: false,
+            speed: 0,
+            steps: 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.position >= self.inputs.len()
+    }
+
+    // Space pauses, '.' steps one tick while paused, and +/- change the
+    // speed. False if the key isn't a playback control.
+    pub fn control(&mut self, key: Key) -> bool {
+        match key {
+            Key::Char(' ') => self.paused = !self.paused,
+            Key::Char('.') => {
+                self.paused = true;
+                self.steps += 1;
+            }
+            Key::Char('+') | Key::Char('=') => self.speed = ::std::cmp::min(self.speed + 1, SPEEDS.len() - 1),
+            Key::Char('-') => self.speed = self.speed.saturating_sub(1),
+            _ => return false,
+        }
+        true
+    }
+
+    // The inputs up to and including the next few ticks, depending on the
+    // speed; while paused, only the ticks stepped through. Called once per
+    // real tick.
+    pub fn next_inputs(&mut self) -> Vec<Input> {
+        let ticks = if !self.paused {
+            SPEEDS[self.speed]
+        }
+        else {
+            ::std::mem::replace(&mut self.steps, 0)
+        };
+
+        let mut result = vec![];
+        let mut played = 0;
+        while played < ticks && !self.finished() {
+            let input = self.inputs[self.position].clone();
+            self.position += 1;
+            if let Input::Tick = input {
+                played += 1;
+                self.ticks += 1;
+            }
+            result.push(input);
+        }
+        result
+    }
+
+    pub fn status(&self) -> String {
+        let total = self.inputs.iter().filter(|input| match **input { Input::Tick => true, _ => false }).count();
+        let state = if self.finished() {
+            "DONE".to_owned()
+        }
+        else if self.paused {
+            "PAUSED".to_owned()
+        }
+        else {
+            format!("{}x", SPEEDS[self.speed])
+        };
+        format!(" REPLAY {} {}/{} SPC . + - ", state, self.ticks, total)
+    }
+}